@@ -1,54 +1,129 @@
-use std::{
-    ops::{Mul, Sub},
-    str::FromStr,
-};
+use std::ops::Sub;
 
 use cosmwasm_bignumber::{Decimal256, Uint256};
 use cosmwasm_std::{
-    coins, log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Decimal, Env,
-    Extern, HandleResponse, HandleResult, HumanAddr, InitResponse, Querier, StdError, StdResult,
-    Storage, Uint128, WasmMsg,
+    from_binary, log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Decimal,
+    Env, Extern, HandleResponse, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage,
+    Uint128, WasmMsg,
 };
-use terra_cosmwasm::{create_swap_msg, TerraMsg, TerraMsgWrapper};
+use terra_cosmwasm::{create_swap_msg, TerraMsgWrapper};
 
 use crate::{
-    math::decimal_division,
-    msg::{ConfigResponse, HandleMsg, InitMsg, QueryMsg, StateResponse},
-    state::{read_config, read_state, store_config, store_state, Config, State},
+    error::ContractError,
+    math::{ceil_multiply_ratio, decimal_division, decimal_ratio},
+    msg::{
+        BasketComponent, ComponentNavResponse, ConfigResponse, ConfigUpdate, Cw20HookMsg,
+        FeeConfigResponse, HandleMsg, InitMsg, NavResponse, Operation, PauseFlagsResponse,
+        PendingConfigResponse, PriceResponse, PricesResponse, QueryMsg, ReserveResponse,
+        SimulateBurnResponse, SimulateMintResponse, SlippageLimit, StateResponse, SwapRateResponse,
+        SwapRoute, TwapResponse,
+    },
+    pricing::{
+        query_nav, query_price, query_reference_price, query_route_price, query_valuation_price,
+        query_value, record_prices,
+    },
+    querier::{query_component_balance, query_component_pair, simulate_component_swap},
+    state::{
+        read_config, read_pause_flags, read_pending_config, read_pending_owner, read_price_history,
+        read_state, store_config, store_pause_flags, store_pending_config, store_pending_owner,
+        store_price_history, store_state, Config, PauseFlags, PendingConfig, PendingOwner,
+        PriceHistory, Reserve, State,
+    },
 };
-use cw20::{Cw20CoinHuman, Cw20HandleMsg, MinterResponse};
+use cw20::{Cw20CoinHuman, Cw20HandleMsg, Cw20ReceiveMsg, MinterResponse};
 use terraswap::{
-    asset::{Asset, AssetInfo, PairInfo},
+    asset::{Asset, AssetInfo, AssetInfoRaw},
     hook::InitHook,
-    pair::{Cw20HookMsg as TerraswapCw20HookMsg, HandleMsg as TerraswapHandleMsg},
-    querier::{query_balance, query_token_balance},
+    pair::{
+        Cw20HookMsg as TerraswapCw20HookMsg, HandleMsg as TerraswapHandleMsg, SimulationResponse,
+    },
+    querier::query_balance,
+    token::InitMsg as TokenInitMsg,
 };
-use terraswap::{querier::query_pair_info, token::InitMsg as TokenInitMsg};
+
+/// Basket weights are expressed in basis points of the deposit
+const BASIS_POINTS: u128 = 10000;
+/// The management fee is quoted per 365 day year
+const SECONDS_PER_YEAR: u64 = 31_536_000;
+/// Longest a config update can be held back, 30 days
+const MAX_CONFIG_TIMELOCK: u64 = 2_592_000;
+/// Longest TWAP window, a day, so a raised window refills its history within one
+const MAX_TWAP_WINDOW: u64 = 86_400;
+/// Share of the value sold in a rebalance the bounty can take, in basis points
+const MAX_BOUNTY_SHARE: u128 = 100;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    validate_stable_denom(&msg.stable_denom)?;
+    validate_basket(&msg.basket)?;
+    if let Some(max_spread) = msg.max_spread {
+        if max_spread > Decimal::one() {
+            return Err(ContractError::InvalidMaxSpread {}.into());
+        }
+    }
+    validate_tolerance(msg.rebalance_tolerance)?;
+    validate_price_deviation(msg.max_price_deviation)?;
+    validate_fee(msg.mint_fee)?;
+    validate_fee(msg.burn_fee)?;
+    validate_fee(msg.management_fee)?;
+    validate_timelock(msg.config_timelock)?;
+    validate_twap_window(msg.twap_window)?;
+
+    let mut basket = vec![];
+    let mut reserves = vec![];
+    let mut price_history = vec![];
+    for component in msg.basket.iter() {
+        let component = component.to_raw(deps)?;
+        reserves.push(Reserve {
+            info: component.info.clone(),
+            amount: Decimal256::zero(),
+        });
+        price_history.push(PriceHistory {
+            info: component.info.clone(),
+            observations: vec![],
+        });
+        basket.push(component);
+    }
+
     let config = Config {
         owner_addr: deps.api.canonical_address(&env.message.sender)?,
+        guardian: msg
+            .guardian
+            .map(|guardian| deps.api.canonical_address(&guardian))
+            .transpose()?,
+        contract_addr: deps.api.canonical_address(&env.contract.address)?,
         terraswap_factory: deps.api.canonical_address(&msg.terraswap_factory)?,
-        alloc_luna: msg.alloc_luna,
-        alloc_anc: msg.alloc_anc,
-        alloc_mir: msg.alloc_mir,
         stable_denom: msg.stable_denom.clone(),
         eterra_contract: CanonicalAddr::default(),
+        basket,
+        max_spread: msg.max_spread,
+        max_price_deviation: msg.max_price_deviation,
+        twap_window: msg.twap_window,
+        rebalance_tolerance: msg.rebalance_tolerance,
+        rebalance_interval: msg.rebalance_interval,
+        rebalance_bounty: msg.rebalance_bounty,
+        mint_fee: msg.mint_fee,
+        burn_fee: msg.burn_fee,
+        management_fee: msg.management_fee,
+        fee_collector: deps.api.canonical_address(&msg.fee_collector)?,
+        config_timelock: msg.config_timelock,
     };
+    validate_reference_prices(deps, &config)?;
 
     let state = State {
         total_supply: Uint128::zero(),
-        reserve_luna: Decimal256::zero(),
-        reserve_anc: Decimal256::zero(),
-        reserve_mir: Decimal256::zero(),
+        reserves,
+        last_fee_accrual: env.block.time,
+        last_rebalance: env.block.time,
     };
 
     store_config(&mut deps.storage).save(&config)?;
     store_state(&mut deps.storage).save(&state)?;
+    store_pause_flags(&mut deps.storage).save(&PauseFlags::default())?;
+    store_price_history(&mut deps.storage).save(&price_history)?;
 
     Ok(InitResponse {
         messages: vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
@@ -77,25 +152,197 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn validate_stable_denom(denom: &str) -> Result<(), ContractError> {
+    // native denoms are micro units like "uusd", the token name is derived from the rest
+    if denom.len() < 3 || !denom.starts_with('u') || !denom.chars().all(|c| c.is_ascii_lowercase())
+    {
+        return Err(ContractError::InvalidStableDenom {
+            denom: denom.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_fee(fee: u64) -> Result<(), ContractError> {
+    if u128::from(fee) > BASIS_POINTS {
+        return Err(ContractError::InvalidFee {
+            fee,
+            max: BASIS_POINTS,
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_tolerance(tolerance: u64) -> Result<(), ContractError> {
+    if u128::from(tolerance) > BASIS_POINTS {
+        return Err(ContractError::InvalidTolerance {
+            tolerance,
+            max: BASIS_POINTS,
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_price_deviation(deviation: u64) -> Result<(), ContractError> {
+    if u128::from(deviation) > BASIS_POINTS {
+        return Err(ContractError::InvalidPriceDeviation {
+            deviation,
+            max: BASIS_POINTS,
+        });
+    }
+
+    Ok(())
+}
+
+/// The price bound needs a reference for every component, which tokens only get
+/// from the TWAP
+fn validate_reference_prices<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+) -> Result<(), ContractError> {
+    if config.max_price_deviation == 0 || config.twap_window != 0 {
+        return Ok(());
+    }
+
+    for component in config.basket.iter() {
+        if let AssetInfoRaw::Token { .. } = component.info {
+            return Err(ContractError::NoReferencePrice {
+                asset: component.info.to_normal(deps)?.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_timelock(timelock: u64) -> Result<(), ContractError> {
+    if timelock > MAX_CONFIG_TIMELOCK {
+        return Err(ContractError::InvalidTimelock {
+            timelock,
+            max: MAX_CONFIG_TIMELOCK,
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_twap_window(twap_window: u64) -> Result<(), ContractError> {
+    if twap_window > MAX_TWAP_WINDOW {
+        return Err(ContractError::InvalidTwapWindow {
+            twap_window,
+            max: MAX_TWAP_WINDOW,
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_basket(basket: &[BasketComponent]) -> Result<(), ContractError> {
+    if basket.is_empty() {
+        return Err(ContractError::EmptyBasket {});
+    }
+
+    let mut total_weight = Decimal256::zero();
+    for (i, component) in basket.iter().enumerate() {
+        if component.weight.is_zero() {
+            return Err(ContractError::ZeroWeight {
+                asset: component.info.to_string(),
+            });
+        }
+        if component.route == (SwapRoute::Market {}) && !component.info.is_native_token() {
+            return Err(ContractError::MarketRouteNotNative {
+                asset: component.info.to_string(),
+            });
+        }
+        if basket[..i].iter().any(|c| c.info.equal(&component.info)) {
+            return Err(ContractError::DuplicateComponent {
+                asset: component.info.to_string(),
+            });
+        }
+        total_weight += component.weight;
+    }
+
+    if total_weight != Decimal256::from_uint256(BASIS_POINTS) {
+        return Err(ContractError::AllocationSumInvalid {
+            expected: BASIS_POINTS,
+            total: total_weight,
+        });
+    }
+
+    Ok(())
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse<TerraMsgWrapper>> {
-    match msg {
-        HandleMsg::Mint {} => mint(deps, env),
+    let res = match msg {
+        HandleMsg::Receive(msg) => receive_cw20(deps, env, msg),
+        HandleMsg::Mint { limits } => mint(deps, env, limits),
         HandleMsg::RegisterETerra {} => register_eterra(deps, env),
-        HandleMsg::Burn {} => burn(deps, env),
-        HandleMsg::RedeemToken { sender } => redeem_token(deps, env, sender),
+        HandleMsg::MintInKind { amount } => mint_in_kind(deps, env, amount),
+        HandleMsg::RedeemToken {
+            sender,
+            prev_balance,
+        } => redeem_token(deps, env, sender, prev_balance),
+        HandleMsg::UpdateReserves { prev_balances } => update_reserves(deps, env, prev_balances),
+        HandleMsg::Reconcile { resync } => reconcile(deps, env, resync),
+        HandleMsg::UpdateConfig(update) => update_config(deps, env, update),
+        HandleMsg::ExecuteQueuedConfig {} => execute_queued_config(deps, env),
+        HandleMsg::CancelQueuedConfig {} => cancel_queued_config(deps, env),
+        HandleMsg::UpdateGuardian { guardian } => update_guardian(deps, env, guardian),
+        HandleMsg::ProposeNewOwner {
+            owner,
+            expires_in_blocks,
+        } => propose_new_owner(deps, env, owner, expires_in_blocks),
+        HandleMsg::AcceptOwnership {} => accept_ownership(deps, env),
+        HandleMsg::CancelOwnershipProposal {} => cancel_ownership_proposal(deps, env),
+        HandleMsg::CollectFees {} => collect_fees(deps, env),
+        HandleMsg::Rebalance { limits } => rebalance(deps, env, limits),
+        HandleMsg::PokeRebalance {} => poke_rebalance(deps, env),
+        HandleMsg::RecordPrices {} => handle_record_prices(deps, env),
+        HandleMsg::Pause { operations } => set_paused(deps, env, operations, true),
+        HandleMsg::Unpause { operations } => set_paused(deps, env, operations, false),
+    };
+
+    res.map_err(StdError::from)
+}
+
+pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    if let Some(msg) = cw20_msg.msg {
+        match from_binary(&msg)? {
+            Cw20HookMsg::Burn { limits } => {
+                // only eTerra can be redeemed
+                let config = read_config(&deps.storage).load()?;
+                if config.eterra_contract == CanonicalAddr::default() {
+                    return Err(ContractError::NotRegistered {});
+                }
+                if deps.api.canonical_address(&env.message.sender)? != config.eterra_contract {
+                    return Err(ContractError::Unauthorized {});
+                }
+
+                burn(deps, env, cw20_msg.sender, cw20_msg.amount, limits)
+            }
+        }
+    } else {
+        Err(ContractError::MissingHookMsg {})
     }
 }
 pub fn register_eterra<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> HandleResult<TerraMsgWrapper> {
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
     let mut state = read_config(&deps.storage).load()?;
     if state.eterra_contract != CanonicalAddr::default() {
-        return Err(StdError::unauthorized());
+        return Err(ContractError::Unauthorized {});
     }
 
     state.eterra_contract = deps.api.canonical_address(&env.message.sender)?;
@@ -109,125 +356,119 @@ pub fn register_eterra<S: Storage, A: Api, Q: Querier>(
 pub fn mint<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse<TerraMsgWrapper>> {
+    limits: Option<Vec<SlippageLimit>>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    assert_not_paused(&deps.storage, Operation::Mint)?;
     let config = read_config(&deps.storage).load()?;
+    if config.eterra_contract == CanonicalAddr::default() {
+        return Err(ContractError::NotRegistered {});
+    }
+    if config.twap_window != 0 {
+        record_prices(deps, &config, env.block.time)?;
+    }
+    assert_prices_within_bound(deps, &config, env.block.time, Operation::Mint)?;
     let mut state: State = read_state(&deps.storage).load()?;
+    let mut messages = vec![];
+
+    // dilute existing holders before the deposit is priced
+    let management_fee = accrue_management_fee(&env, &config, &mut state)?;
+    if let Some(msg) = management_fee_msg(deps, &config, management_fee)? {
+        messages.push(msg);
+    }
 
     // check base denom deposit
-    let deposit_amount: Uint256 = env
+    let deposit_amount: Uint128 = env
         .message
         .sent_funds
         .iter()
         .find(|c| c.denom == config.stable_denom)
-        .map(|c| Uint256::from(c.amount))
-        .unwrap_or_else(Uint256::zero);
+        .map(|c| c.amount)
+        .unwrap_or_else(Uint128::zero);
     // Cannot deposit zero amount
     if deposit_amount.is_zero() {
-        return Err(StdError::generic_err(format!(
-            "Deposit amount must be greater than 0 {}",
-            config.stable_denom,
-        )));
+        return Err(ContractError::ZeroDeposit {
+            denom: config.stable_denom,
+        });
     }
-    let mut mint_amount = Uint128(0);
+    let mut invest_value = Uint128(0);
     let terraswap_factory_raw = deps.api.human_address(&config.terraswap_factory)?;
-    let mut messages = vec![];
-    // swap stable denom => anc
-    let pair_info: PairInfo = query_pair_info(
-        &deps,
-        &terraswap_factory_raw,
-        &[
-            AssetInfo::NativeToken {
-                denom: config.stable_denom.clone().to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("terra1747mad58h0w4y589y3sk84r5efqdev9q4r02pc"),
-            },
-        ],
-    )?;
     let swap_asset = Asset {
         info: AssetInfo::NativeToken {
             denom: config.stable_denom.clone(),
         },
-        amount: deposit_amount.into(),
+        amount: deposit_amount,
     };
-    let amount = (swap_asset.deduct_tax(&deps)?).amount;
-    let amount = decimal_division(
-        amount * config.alloc_anc.into(),
-        Decimal::from_str("10000")?,
-    );
-    mint_amount += amount;
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: pair_info.contract_addr,
-        msg: to_binary(&TerraswapHandleMsg::Swap {
-            offer_asset: Asset {
+
+    // the mint fee is taken off the top, only the rest is allocated
+    let fee_amount = deposit_amount.multiply_ratio(config.mint_fee, BASIS_POINTS);
+    let deposit_amount = deposit_amount.sub(fee_amount)?;
+    if !fee_amount.is_zero() {
+        messages.push(stable_send_msg(
+            deps,
+            &config,
+            env.contract.address.clone(),
+            deps.api.human_address(&config.fee_collector)?,
+            fee_amount,
+        )?);
+    }
+
+    // swap stable denom => each basket component, every transfer paying its own tax
+    for (component, amount) in allocate_deposit(deps, &config, deposit_amount)? {
+        let amount = Asset {
+            info: swap_asset.info.clone(),
+            amount,
+        }
+        .deduct_tax(deps)?
+        .amount;
+        let simulation = simulate_component_swap(
+            deps,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            &component,
+            &Asset {
+                info: swap_asset.info.clone(),
                 amount,
-                info: AssetInfo::NativeToken {
-                    denom: config.stable_denom.clone(),
-                },
             },
-            max_spread: None,
-            belief_price: None,
-            to: None,
-        })?,
-        send: vec![Coin {
-            denom: config.stable_denom.clone(),
-            amount,
-        }],
-    }));
+        )?;
+        invest_value += bought_value(
+            deps,
+            &terraswap_factory_raw,
+            &config,
+            &component,
+            simulation.return_amount,
+            Some(env.block.time),
+        )?;
 
-    // swap stable denom => mirror
-    let pair_info: PairInfo = query_pair_info(
-        &deps,
-        &terraswap_factory_raw,
-        &[
-            AssetInfo::NativeToken {
-                denom: config.stable_denom.clone().to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("terra10llyp6v3j3her8u3ce66ragytu45kcmd9asj3u"),
-            },
-        ],
-    )?;
-    let amount = (swap_asset.deduct_tax(&deps)?).amount;
-    let amount = decimal_division(
-        amount * config.alloc_mir.into(),
-        Decimal::from_str("10000")?,
-    );
-    mint_amount += amount;
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: pair_info.contract_addr,
-        msg: to_binary(&TerraswapHandleMsg::Swap {
-            offer_asset: Asset {
-                amount,
-                info: AssetInfo::NativeToken {
-                    denom: config.stable_denom.clone(),
+        let limit = find_slippage_limit(&limits, &component.info);
+        if let Some(min_out) = limit.and_then(|l| l.min_out) {
+            assert_min_out(
+                deps,
+                &terraswap_factory_raw,
+                &config.stable_denom,
+                &component,
+                &Asset {
+                    info: swap_asset.info.clone(),
+                    amount,
                 },
-            },
-            max_spread: None,
-            belief_price: None,
-            to: None,
-        })?,
-        send: vec![Coin {
-            denom: config.stable_denom.clone(),
+                min_out,
+            )?;
+        }
+        messages.push(buy_component_msg(
+            deps,
+            &env,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            &component,
             amount,
-        }],
-    }));
+            limit.and_then(|l| l.max_spread).or(config.max_spread),
+        )?);
+    }
+    messages.push(update_reserves_msg(deps, &env.contract.address, &config)?);
 
-    // swap stable denom => luna denom
-    let amount = (swap_asset.deduct_tax(&deps)?).amount;
-    let amount = decimal_division(
-        amount * config.alloc_luna.into(),
-        Decimal::from_str("10000")?,
-    );
-    mint_amount += amount;
-    messages.push(create_swap_msg(
-        env.contract.address,
-        Coin {
-            denom: config.stable_denom.clone(),
-            amount,
-        },
-        "uluna".to_string(),
-    ));
+    // price eTerra against the basket held before this deposit is swapped, and the
+    // deposit by what the swaps bring in, so the minter bears their costs
+    let nav = query_nav(deps, &env.contract.address, &config, Some(env.block.time))?;
+    let mint_amount = compute_mint_amount(invest_value, state.total_supply, nav)?;
 
     // mint eTerra
     messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -235,7 +476,7 @@ pub fn mint<S: Storage, A: Api, Q: Querier>(
         send: vec![],
         msg: to_binary(&Cw20HandleMsg::Mint {
             recipient: env.message.sender.clone(),
-            amount: mint_amount.into(),
+            amount: mint_amount,
         })?,
     }));
 
@@ -244,109 +485,237 @@ pub fn mint<S: Storage, A: Api, Q: Querier>(
 
     Ok(HandleResponse {
         messages,
-        log: vec![log("mint", mint_amount.to_string())],
+        log: vec![
+            log("mint", mint_amount.to_string()),
+            log("nav", nav.to_string()),
+            log("fee", fee_amount.to_string()),
+            log("management_fee", management_fee.to_string()),
+        ],
         data: None,
     })
 }
 
-pub fn burn<S: Storage, A: Api, Q: Querier>(
+pub fn mint_in_kind<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse<TerraMsgWrapper>> {
+    amount: Uint128,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    assert_not_paused(&deps.storage, Operation::Mint)?;
     let config = read_config(&deps.storage).load()?;
-    let mut state = read_state(&deps.storage).load()?;
-    let available_amount = query_token_balance(
-        &deps,
-        &deps.api.human_address(&config.eterra_contract)?,
-        &env.contract.address,
-    )?;
-    let terraswap_factory_raw = deps.api.human_address(&config.terraswap_factory)?;
-    let mut messages: Vec<CosmosMsg<TerraMsgWrapper>> = vec![];
+    if config.eterra_contract == CanonicalAddr::default() {
+        return Err(ContractError::NotRegistered {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::ZeroDeposit {
+            denom: "eTerra".to_string(),
+        });
+    }
+    let mut state: State = read_state(&deps.storage).load()?;
+    let eterra_contract = deps.api.human_address(&config.eterra_contract)?;
+    let mut messages = vec![];
 
-    // anc
-    let pair_info: PairInfo = query_pair_info(
-        &deps,
-        &terraswap_factory_raw,
-        &[
-            AssetInfo::NativeToken {
-                denom: config.stable_denom.to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("terra1747mad58h0w4y589y3sk84r5efqdev9q4r02pc"),
-            },
-        ],
-    )?;
-    let balance_anc = query_token_balance(
-        &deps,
-        &HumanAddr::from("terra1747mad58h0w4y589y3sk84r5efqdev9q4r02pc"),
-        &env.contract.address,
-    )?;
+    // the deposit buys into the supply including the accrued fee
+    let management_fee = accrue_management_fee(&env, &config, &mut state)?;
+    if let Some(msg) = management_fee_msg(deps, &config, management_fee)? {
+        messages.push(msg);
+    }
+    if state.total_supply.is_zero() {
+        return Err(ContractError::EmptyFund {});
+    }
 
-    let amount = decimal_division(
-        available_amount.multiply_ratio(balance_anc, state.total_supply) * config.alloc_anc.into(),
-        Decimal::from_str("10000")?,
-    );
+    // the share of each component held, rounded up in favour of existing holders
+    let mut logs = vec![];
+    let mut refunds = vec![];
+    for reserve in state.reserves.iter_mut() {
+        let info = reserve.info.to_normal(deps)?;
+        let balance = query_component_balance(deps, &env.contract.address, &info)?;
+
+        let required = match &info {
+            AssetInfo::NativeToken { denom } => {
+                // sent funds are credited before the handler runs
+                let sent = env
+                    .message
+                    .sent_funds
+                    .iter()
+                    .find(|c| &c.denom == denom)
+                    .map(|c| c.amount)
+                    .unwrap_or_else(Uint128::zero);
+                let balance = balance.sub(sent)?;
+                let required = ceil_multiply_ratio(balance, amount, state.total_supply);
+                if sent < required {
+                    return Err(ContractError::InsufficientDeposit {
+                        asset: info.to_string(),
+                        amount: sent,
+                        required,
+                    });
+                }
+                let excess = sent.sub(required)?;
+                if !excess.is_zero() {
+                    refunds.push(Coin {
+                        denom: denom.to_string(),
+                        amount: excess,
+                    });
+                }
+                required
+            }
+            AssetInfo::Token { contract_addr } => {
+                let required = ceil_multiply_ratio(balance, amount, state.total_supply);
+                if !required.is_zero() {
+                    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: contract_addr.clone(),
+                        send: vec![],
+                        msg: to_binary(&Cw20HandleMsg::TransferFrom {
+                            owner: env.message.sender.clone(),
+                            recipient: env.contract.address.clone(),
+                            amount: required,
+                        })?,
+                    }));
+                }
+                required
+            }
+        };
+
+        reserve.amount += Decimal256::from_uint256(required);
+        logs.push(log("deposit", format!("{}:{}", info, required)));
+    }
+    // denoms outside the basket are sent back too
+    for coin in env.message.sent_funds.iter() {
+        let is_component = state.reserves.iter().any(|r| {
+            r.info.equal(&AssetInfoRaw::NativeToken {
+                denom: coin.denom.clone(),
+            })
+        });
+        if !is_component && !coin.amount.is_zero() {
+            refunds.push(coin.clone());
+        }
+    }
+    // net of the tax on sending them, which the fund would pay otherwise
+    let refunds = refunds
+        .into_iter()
+        .map(|coin| {
+            Asset {
+                info: AssetInfo::NativeToken { denom: coin.denom },
+                amount: coin.amount,
+            }
+            .deduct_tax(deps)
+        })
+        .filter(|coin| coin.as_ref().map_or(true, |coin| !coin.amount.is_zero()))
+        .collect::<StdResult<Vec<Coin>>>()?;
+    if !refunds.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: env.message.sender.clone(),
+            amount: refunds,
+        }));
+    }
+
+    // the mint fee is paid in eTerra out of the minted amount
+    let fee_amount = amount.multiply_ratio(config.mint_fee, BASIS_POINTS);
     messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps.api.human_address(&config.eterra_contract)?,
-        msg: to_binary(&Cw20HandleMsg::Send {
-            contract: pair_info.contract_addr,
-            amount,
-            msg: Some(to_binary(&TerraswapCw20HookMsg::Swap {
-                max_spread: None,
-                belief_price: None,
-                to: None,
-            })?),
-        })?,
+        contract_addr: eterra_contract.clone(),
         send: vec![],
-    }));
-    // mirror
-    let pair_info: PairInfo = query_pair_info(
-        &deps,
-        &terraswap_factory_raw,
-        &[
-            AssetInfo::NativeToken {
-                denom: config.stable_denom.to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("terra10llyp6v3j3her8u3ce66ragytu45kcmd9asj3u"),
-            },
-        ],
-    )?;
-    let amount = decimal_division(
-        available_amount.multiply_ratio(balance_anc, state.total_supply) * config.alloc_mir.into(),
-        Decimal::from_str("10000")?,
-    );
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps.api.human_address(&config.eterra_contract)?,
-        msg: to_binary(&Cw20HandleMsg::Send {
-            contract: pair_info.contract_addr,
-            amount,
-            msg: Some(to_binary(&TerraswapCw20HookMsg::Swap {
-                max_spread: None,
-                belief_price: None,
-                to: None,
-            })?),
+        msg: to_binary(&Cw20HandleMsg::Mint {
+            recipient: env.message.sender,
+            amount: amount.sub(fee_amount)?,
         })?,
-        send: vec![],
     }));
+    if !fee_amount.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: eterra_contract,
+            send: vec![],
+            msg: to_binary(&Cw20HandleMsg::Mint {
+                recipient: deps.api.human_address(&config.fee_collector)?,
+                amount: fee_amount,
+            })?,
+        }));
+    }
 
-    let amount = decimal_division(
-        available_amount.multiply_ratio(balance_anc, state.total_supply) * config.alloc_luna.into(),
-        Decimal::from_str("10000")?,
-    );
-    messages.push(create_swap_msg(
-        env.contract.address.clone(),
-        Coin {
-            denom: "uluna".to_string(),
+    state.total_supply += amount;
+    store_state(&mut deps.storage).save(&state)?;
+
+    logs.push(log("mint", amount.to_string()));
+    logs.push(log("fee", fee_amount.to_string()));
+    logs.push(log("management_fee", management_fee.to_string()));
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: None,
+    })
+}
+
+pub fn burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    burn_amount: Uint128,
+    limits: Option<Vec<SlippageLimit>>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    assert_not_paused(&deps.storage, Operation::Burn)?;
+    let config = read_config(&deps.storage).load()?;
+    if config.twap_window != 0 {
+        record_prices(deps, &config, env.block.time)?;
+    }
+    assert_prices_within_bound(deps, &config, env.block.time, Operation::Burn)?;
+    let mut state = read_state(&deps.storage).load()?;
+    let eterra_contract = deps.api.human_address(&config.eterra_contract)?;
+    let terraswap_factory_raw = deps.api.human_address(&config.terraswap_factory)?;
+    let mut messages: Vec<CosmosMsg<TerraMsgWrapper>> = vec![];
+
+    // the burnt share is taken of the supply including the accrued fee
+    let management_fee = accrue_management_fee(&env, &config, &mut state)?;
+    if let Some(msg) = management_fee_msg(deps, &config, management_fee)? {
+        messages.push(msg);
+    }
+
+    if burn_amount > state.total_supply {
+        return Err(ContractError::BurnExceedsSupply {
+            amount: burn_amount,
+            total_supply: state.total_supply,
+        });
+    }
+
+    // only the proceeds of this burn are redeemed, whatever else is held stays put
+    let prev_balance = query_balance(deps, &env.contract.address, config.stable_denom.clone())?;
+
+    // sell the burnt share of each component actually held by the contract
+    for (component, amount) in allocate_burn(
+        deps,
+        &config,
+        &env.contract.address,
+        burn_amount,
+        state.total_supply,
+    )? {
+        let limit = find_slippage_limit(&limits, &component.info);
+        if let Some(min_out) = limit.and_then(|l| l.min_out) {
+            assert_min_out(
+                deps,
+                &terraswap_factory_raw,
+                &config.stable_denom,
+                &component,
+                &Asset {
+                    info: component.info.clone(),
+                    amount,
+                },
+                min_out,
+            )?;
+        }
+        messages.push(sell_component_msg(
+            deps,
+            &env,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            &component,
             amount,
-        },
-        config.stable_denom.clone(),
-    ));
+            limit.and_then(|l| l.max_spread).or(config.max_spread),
+        )?);
+    }
+    messages.push(update_reserves_msg(deps, &env.contract.address, &config)?);
+
     messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps.api.human_address(&config.eterra_contract)?,
+        contract_addr: eterra_contract,
         send: vec![],
         msg: to_binary(&Cw20HandleMsg::Burn {
-            amount: available_amount.into(),
+            amount: burn_amount,
         })?,
     }));
 
@@ -354,16 +723,20 @@ pub fn burn<S: Storage, A: Api, Q: Querier>(
         contract_addr: env.contract.address,
         send: vec![],
         msg: to_binary(&HandleMsg::RedeemToken {
-            sender: env.message.sender,
+            sender,
+            prev_balance,
         })?,
     }));
 
-    state.total_supply = state.total_supply.sub(available_amount)?;
+    state.total_supply = state.total_supply.sub(burn_amount)?;
     store_state(&mut deps.storage).save(&state)?;
 
     Ok(HandleResponse {
         messages,
-        log: vec![log("burn", available_amount.to_string())],
+        log: vec![
+            log("burn", burn_amount.to_string()),
+            log("management_fee", management_fee.to_string()),
+        ],
         data: None,
     })
 }
@@ -372,485 +745,5225 @@ pub fn redeem_token<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     sender: HumanAddr,
-) -> StdResult<HandleResponse<TerraMsgWrapper>> {
+    prev_balance: Uint128,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    assert_not_paused(&deps.storage, Operation::Burn)?;
     // this is just meant as a call-back to ourself
     if env.message.sender != env.contract.address {
-        return Err(StdError::unauthorized());
+        return Err(ContractError::Unauthorized {});
     }
     let config = read_config(&deps.storage).load()?;
-    let balance = deps
-        .querier
-        .query_balance(env.contract.address.clone(), "uusd")
-        .unwrap()
-        .amount;
+    let balance = query_balance(deps, &env.contract.address, config.stable_denom.clone())?;
+    let amount = balance.sub(prev_balance)?;
+    let fee_amount = amount.multiply_ratio(config.burn_fee, BASIS_POINTS);
+    let amount = amount.sub(fee_amount)?;
+
+    let mut messages = vec![];
+    if !fee_amount.is_zero() {
+        messages.push(stable_send_msg(
+            deps,
+            &config,
+            env.contract.address.clone(),
+            deps.api.human_address(&config.fee_collector)?,
+            fee_amount,
+        )?);
+    }
+    if !amount.is_zero() {
+        messages.push(stable_send_msg(
+            deps,
+            &config,
+            env.contract.address,
+            sender,
+            amount,
+        )?);
+    }
+
     Ok(HandleResponse {
-        messages: vec![CosmosMsg::Bank(BankMsg::Send {
-            from_address: env.contract.address,
-            to_address: sender,
-            amount: coins(balance.into(), &config.stable_denom),
-        })],
-        log: vec![log("redeem", balance)],
+        messages,
+        log: vec![log("redeem", amount), log("fee", fee_amount)],
         data: None,
     })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
+/// Sends `amount` of the stable denom less the tax Terra charges the sender on top,
+/// so the fund pays out no more than `amount`
+fn stable_send_msg<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::State {} => to_binary(&query_state(deps)?),
+    config: &Config,
+    from_address: HumanAddr,
+    to_address: HumanAddr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg<TerraMsgWrapper>> {
+    let coin = Asset {
+        info: AssetInfo::NativeToken {
+            denom: config.stable_denom.clone(),
+        },
+        amount,
     }
-}
+    .deduct_tax(deps)?;
 
-fn query_config<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<ConfigResponse> {
-    let config = read_config(&deps.storage).load()?;
-    Ok(ConfigResponse {
-        terraswap_factory: deps.api.human_address(&config.terraswap_factory)?,
-        owner_addr: deps.api.human_address(&config.owner_addr)?,
-        eterra_contract: deps.api.human_address(&config.eterra_contract)?,
-        stable_denom: config.stable_denom,
-        alloc_luna: config.alloc_luna,
-        alloc_anc: config.alloc_anc,
-        alloc_mir: config.alloc_mir,
-    })
+    Ok(CosmosMsg::Bank(BankMsg::Send {
+        from_address,
+        to_address,
+        amount: vec![coin],
+    }))
 }
 
-fn query_state<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<StateResponse> {
-    let state = read_state(&deps.storage).load()?;
-    Ok(StateResponse {
-        total_supply: state.total_supply,
-        reserve_luna: state.reserve_luna,
-        reserve_anc: state.reserve_anc,
-        reserve_mir: state.reserve_mir,
+pub fn update_reserves<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    prev_balances: Vec<Asset>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    // this is just meant as a call-back to ourself
+    if env.message.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut state = read_state(&deps.storage).load()?;
+
+    let mut logs = vec![];
+    for reserve in state.reserves.iter_mut() {
+        let info = reserve.info.to_normal(deps)?;
+        let prev_balance = match prev_balances.iter().find(|a| a.info.equal(&info)) {
+            Some(asset) => asset.amount,
+            None => continue,
+        };
+        let balance = query_component_balance(deps, &env.contract.address, &info)?;
+        if balance >= prev_balance {
+            reserve.amount += Decimal256::from_uint256(balance.sub(prev_balance)?);
+        } else {
+            let spent = Decimal256::from_uint256(prev_balance.sub(balance)?);
+            reserve.amount = if spent > reserve.amount {
+                Decimal256::zero()
+            } else {
+                reserve.amount - spent
+            };
+        }
+        logs.push(log("reserve", format!("{}:{}", info, reserve.amount)));
+    }
+
+    store_state(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: None,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::mock_querier::mock_dependencies;
+pub fn reconcile<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    resync: Option<bool>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let resync = resync.unwrap_or(false);
+    let config = read_config(&deps.storage).load()?;
+    if resync && deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut state = read_state(&deps.storage).load()?;
 
-    use super::*;
-    use cosmwasm_std::{
-        testing::{mock_env, MockApi, MOCK_CONTRACT_ADDR},
-        HumanAddr,
-    };
-    use terra_cosmwasm::TerraRoute;
+    let mut logs = vec![];
+    for reserve in state.reserves.iter_mut() {
+        let info = reserve.info.to_normal(deps)?;
+        let balance =
+            Decimal256::from_uint256(query_component_balance(deps, &env.contract.address, &info)?);
+        if balance != reserve.amount {
+            logs.push(log(
+                "discrepancy",
+                format!("{}:{}:{}", info, reserve.amount, balance),
+            ));
+            reserve.amount = balance;
+        }
+    }
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(20, &[]);
-        let msg = InitMsg {
-            alloc_luna: Decimal256::from_uint256(5000u128),
-            alloc_mir: Decimal256::from_uint256(2500u128),
-            alloc_anc: Decimal256::from_uint256(2500u128),
-            stable_denom: "uusd".to_string(),
-            eterra_code_id: 123u64,
-            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
-        };
-        let env = mock_env("creator", &[]);
+    // unexplained losses stay visible unless the owner chooses to write them off
+    if resync {
+        store_state(&mut deps.storage).save(&state)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: None,
+    })
+}
+
+pub fn update_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    update: ConfigUpdate,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // reject an update that could never be executed
+    let mut state = read_state(&deps.storage).load()?;
+    apply_config_update(deps, &env, &mut config.clone(), &mut state, update.clone())?;
+
+    let execute_after = env.block.time.checked_add(config.config_timelock).ok_or(
+        ContractError::InvalidTimelock {
+            timelock: config.config_timelock,
+            max: MAX_CONFIG_TIMELOCK,
+        },
+    )?;
+    store_pending_config(&mut deps.storage).save(&PendingConfig {
+        update,
+        execute_after,
+    })?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("execute_after", execute_after)],
+        data: None,
+    })
+}
+
+pub fn execute_queued_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let mut config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let pending = read_pending_config(&deps.storage)
+        .may_load()?
+        .ok_or(ContractError::NoPendingConfig {})?;
+    if env.block.time < pending.execute_after {
+        return Err(ContractError::TimelockNotExpired {
+            execute_after: pending.execute_after,
+        });
+    }
+
+    let mut state = read_state(&deps.storage).load()?;
+    let res = apply_config_update(deps, &env, &mut config, &mut state, pending.update)?;
+    store_config(&mut deps.storage).save(&config)?;
+    store_state(&mut deps.storage).save(&state)?;
+    store_pending_config(&mut deps.storage).remove();
+
+    Ok(res)
+}
+
+pub fn cancel_queued_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if read_pending_config(&deps.storage).may_load()?.is_none() {
+        return Err(ContractError::NoPendingConfig {});
+    }
+    store_pending_config(&mut deps.storage).remove();
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("cancel_queued_config", "")],
+        data: None,
+    })
+}
+
+pub fn update_guardian<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    guardian: Option<HumanAddr>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let mut config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    config.guardian = guardian
+        .as_ref()
+        .map(|guardian| deps.api.canonical_address(guardian))
+        .transpose()?;
+    store_config(&mut deps.storage).save(&config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log(
+            "guardian",
+            guardian.map(|g| g.to_string()).unwrap_or_default(),
+        )],
+        data: None,
+    })
+}
+
+/// Applies `update` to `config` and `state` in memory, the caller saves them
+fn apply_config_update<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    config: &mut Config,
+    state: &mut State,
+    update: ConfigUpdate,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let ConfigUpdate {
+        terraswap_factory,
+        weights,
+        max_price_deviation,
+        twap_window,
+        rebalance_tolerance,
+        rebalance_interval,
+        rebalance_bounty,
+        mint_fee,
+        burn_fee,
+        management_fee,
+        fee_collector,
+        config_timelock,
+    } = update;
+
+    let mut messages = vec![];
+    let mut logs = vec![];
+    if let Some(terraswap_factory) = terraswap_factory {
+        let terraswap_factory_raw = deps.api.canonical_address(&terraswap_factory)?;
+        if terraswap_factory_raw != config.terraswap_factory {
+            config.terraswap_factory = terraswap_factory_raw;
+            logs.push(log("terraswap_factory", terraswap_factory));
+        }
+    }
+
+    if let Some(weights) = weights {
+        for new_weight in weights.iter() {
+            let info = new_weight.info.to_raw(deps)?;
+            let component = config
+                .basket
+                .iter_mut()
+                .find(|c| c.info.equal(&info))
+                .ok_or_else(|| ContractError::NotBasketComponent {
+                    asset: new_weight.info.to_string(),
+                })?;
+            if component.weight != new_weight.weight {
+                component.weight = new_weight.weight;
+                logs.push(log(
+                    "weight",
+                    format!("{}:{}", new_weight.info, new_weight.weight),
+                ));
+            }
+        }
+
+        validate_basket(
+            &config
+                .basket
+                .iter()
+                .map(|c| c.to_normal(deps))
+                .collect::<StdResult<Vec<BasketComponent>>>()?,
+        )?;
+    }
+
+    if let Some(max_price_deviation) = max_price_deviation {
+        validate_price_deviation(max_price_deviation)?;
+        if max_price_deviation != config.max_price_deviation {
+            config.max_price_deviation = max_price_deviation;
+            logs.push(log("max_price_deviation", max_price_deviation));
+        }
+    }
+
+    if let Some(twap_window) = twap_window {
+        validate_twap_window(twap_window)?;
+        if twap_window != config.twap_window {
+            config.twap_window = twap_window;
+            logs.push(log("twap_window", twap_window));
+        }
+    }
+
+    if let Some(rebalance_tolerance) = rebalance_tolerance {
+        validate_tolerance(rebalance_tolerance)?;
+        if rebalance_tolerance != config.rebalance_tolerance {
+            config.rebalance_tolerance = rebalance_tolerance;
+            logs.push(log("rebalance_tolerance", rebalance_tolerance));
+        }
+    }
+
+    if let Some(rebalance_interval) = rebalance_interval {
+        if rebalance_interval != config.rebalance_interval {
+            config.rebalance_interval = rebalance_interval;
+            logs.push(log("rebalance_interval", rebalance_interval));
+        }
+    }
+
+    if let Some(rebalance_bounty) = rebalance_bounty {
+        if rebalance_bounty != config.rebalance_bounty {
+            config.rebalance_bounty = rebalance_bounty;
+            logs.push(log("rebalance_bounty", rebalance_bounty));
+        }
+    }
+
+    if let Some(mint_fee) = mint_fee {
+        validate_fee(mint_fee)?;
+        if mint_fee != config.mint_fee {
+            config.mint_fee = mint_fee;
+            logs.push(log("mint_fee", mint_fee));
+        }
+    }
+
+    if let Some(burn_fee) = burn_fee {
+        validate_fee(burn_fee)?;
+        if burn_fee != config.burn_fee {
+            config.burn_fee = burn_fee;
+            logs.push(log("burn_fee", burn_fee));
+        }
+    }
+
+    if let Some(management_fee) = management_fee {
+        validate_fee(management_fee)?;
+        if management_fee != config.management_fee {
+            // settle what accrued at the old rate, paid to the current collector
+            let accrued = accrue_management_fee(env, config, state)?;
+            if let Some(msg) = management_fee_msg(deps, config, accrued)? {
+                messages.push(msg);
+            }
+
+            config.management_fee = management_fee;
+            logs.push(log("management_fee", management_fee));
+        }
+    }
+
+    if let Some(fee_collector) = fee_collector {
+        let fee_collector_raw = deps.api.canonical_address(&fee_collector)?;
+        if fee_collector_raw != config.fee_collector {
+            config.fee_collector = fee_collector_raw;
+            logs.push(log("fee_collector", fee_collector));
+        }
+    }
+
+    if let Some(config_timelock) = config_timelock {
+        validate_timelock(config_timelock)?;
+        if config_timelock != config.config_timelock {
+            config.config_timelock = config_timelock;
+            logs.push(log("config_timelock", config_timelock));
+        }
+    }
+
+    validate_reference_prices(deps, config)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: None,
+    })
+}
+
+pub fn propose_new_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: HumanAddr,
+    expires_in_blocks: u64,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let expires_at = env
+        .block
+        .height
+        .checked_add(expires_in_blocks)
+        .ok_or(ContractError::InvalidExpiry { expires_in_blocks })?;
+    store_pending_owner(&mut deps.storage).save(&PendingOwner {
+        owner: deps.api.canonical_address(&owner)?,
+        expires_at,
+    })?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("pending_owner", owner),
+            log("expires_at", expires_at.to_string()),
+        ],
+        data: None,
+    })
+}
+
+pub fn accept_ownership<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let pending_owner = read_pending_owner(&deps.storage)
+        .may_load()?
+        .ok_or(ContractError::NoPendingOwner {})?;
+    if deps.api.canonical_address(&env.message.sender)? != pending_owner.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if env.block.height > pending_owner.expires_at {
+        return Err(ContractError::OwnershipProposalExpired {});
+    }
+
+    let mut config = read_config(&deps.storage).load()?;
+    config.owner_addr = pending_owner.owner;
+    store_config(&mut deps.storage).save(&config)?;
+    store_pending_owner(&mut deps.storage).remove();
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("owner", env.message.sender)],
+        data: None,
+    })
+}
+
+pub fn cancel_ownership_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+    if read_pending_owner(&deps.storage).may_load()?.is_none() {
+        return Err(ContractError::NoPendingOwner {});
+    }
+
+    store_pending_owner(&mut deps.storage).remove();
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("cancel_ownership_proposal", env.message.sender)],
+        data: None,
+    })
+}
+
+pub fn handle_record_prices<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    let prices = record_prices(deps, &config, env.block.time)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: prices
+            .into_iter()
+            .map(|(info, price)| log("price", format!("{}:{}", info, price)))
+            .collect(),
+        data: None,
+    })
+}
+
+pub fn set_paused<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    operations: Option<Vec<Operation>>,
+    paused: bool,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    // the guardian may stop the fund, only the owner may resume it
+    let config = read_config(&deps.storage).load()?;
+    let sender_raw = deps.api.canonical_address(&env.message.sender)?;
+    let is_guardian = config.guardian.as_ref() == Some(&sender_raw);
+    if sender_raw != config.owner_addr && !(paused && is_guardian) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let operations = operations.unwrap_or_else(|| Operation::ALL.to_vec());
+    let mut flags = read_pause_flags(&deps.storage).load()?;
+    for operation in operations.iter() {
+        flags.set(*operation, paused);
+    }
+    store_pause_flags(&mut deps.storage).save(&flags)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log(
+            if paused { "pause" } else { "unpause" },
+            operations
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+        )],
+        data: None,
+    })
+}
+
+pub fn collect_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if config.eterra_contract == CanonicalAddr::default() {
+        return Err(ContractError::NotRegistered {});
+    }
+    let mut state = read_state(&deps.storage).load()?;
+
+    let management_fee = accrue_management_fee(&env, &config, &mut state)?;
+    let messages = management_fee_msg(deps, &config, management_fee)?
+        .into_iter()
+        .collect();
+    store_state(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("management_fee", management_fee.to_string())],
+        data: None,
+    })
+}
+
+pub fn rebalance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limits: Option<Vec<SlippageLimit>>,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    let config = read_config(&deps.storage).load()?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+    assert_not_paused(&deps.storage, Operation::Rebalance)?;
+
+    let (res, _) = rebalance_msgs(
+        deps,
+        &env,
+        &config,
+        &limits,
+        config.rebalance_tolerance,
+        Uint128::zero(),
+        None,
+    )?;
+
+    let mut state = read_state(&deps.storage).load()?;
+    state.last_rebalance = env.block.time;
+    store_state(&mut deps.storage).save(&state)?;
+
+    Ok(res)
+}
+
+pub fn poke_rebalance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse<TerraMsgWrapper>, ContractError> {
+    assert_not_paused(&deps.storage, Operation::Rebalance)?;
+    let config = read_config(&deps.storage).load()?;
+    // anyone can trigger the swaps, so they must be bounded by reference prices
+    if config.max_price_deviation == 0 {
+        return Err(ContractError::UnboundedRebalance {});
+    }
+    if config.twap_window != 0 {
+        record_prices(deps, &config, env.block.time)?;
+    }
+    assert_prices_within_bound(deps, &config, env.block.time, Operation::Rebalance)?;
+    let mut state = read_state(&deps.storage).load()?;
+
+    // once the interval has elapsed any drift may be corrected, before that only
+    // drift beyond the tolerance band
+    let tolerance = if config.rebalance_interval != 0
+        && env.block.time
+            >= state
+                .last_rebalance
+                .saturating_add(config.rebalance_interval)
+    {
+        0
+    } else {
+        config.rebalance_tolerance
+    };
+    let (mut res, bounty) = rebalance_msgs(
+        deps,
+        &env,
+        &config,
+        &None,
+        tolerance,
+        config.rebalance_bounty,
+        Some(decimal_ratio(
+            Uint128::from(config.max_price_deviation),
+            Uint128(BASIS_POINTS),
+        )),
+    )?;
+
+    if !bounty.is_zero() {
+        res.messages.push(stable_send_msg(
+            deps,
+            &config,
+            env.contract.address.clone(),
+            env.message.sender.clone(),
+            bounty,
+        )?);
+    }
+    res.log.push(log("bounty", bounty));
+
+    state.last_rebalance = env.block.time;
+    store_state(&mut deps.storage).save(&state)?;
+
+    Ok(res)
+}
+
+/// Once any component drifts from its target by more than `tolerance` basis points of
+/// the NAV, sells every over-weight component into the stable denom and spends the
+/// simulated proceeds on the under-weight ones, pro rata to their shortfall. Up to
+/// `bounty` of the proceeds, capped at `MAX_BOUNTY_SHARE`, is held back and returned
+/// alongside the swaps. With `max_deviation` every swap must return at least its
+/// reference price less that share.
+fn rebalance_msgs<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+    limits: &Option<Vec<SlippageLimit>>,
+    tolerance: u64,
+    bounty: Uint128,
+    max_deviation: Option<Decimal256>,
+) -> Result<(HandleResponse<TerraMsgWrapper>, Uint128), ContractError> {
+    let terraswap_factory_raw = deps.api.human_address(&config.terraswap_factory)?;
+
+    let mut positions = vec![];
+    let mut nav = Uint128::zero();
+    for component in config.basket.iter() {
+        let component = component.to_normal(deps)?;
+        let balance = query_component_balance(deps, &env.contract.address, &component.info)?;
+        let value = query_value(
+            deps,
+            &terraswap_factory_raw,
+            config,
+            &component.info,
+            balance,
+            Some(env.block.time),
+        )?;
+        nav += value;
+        positions.push((component, balance, value));
+    }
+    if nav.is_zero() {
+        return Err(ContractError::ZeroNav {});
+    }
+
+    let band = nav.multiply_ratio(tolerance, BASIS_POINTS);
+    let positions = positions
+        .into_iter()
+        .map(|(component, balance, value)| {
+            let target = decimal_division(
+                nav * component.weight.into(),
+                Decimal::from_ratio(BASIS_POINTS, 1u128),
+            );
+            (component, balance, value, target)
+        })
+        .collect::<Vec<_>>();
+    if positions.iter().all(|(_, _, value, target)| {
+        let drift = if value > target {
+            value.u128() - target.u128()
+        } else {
+            target.u128() - value.u128()
+        };
+        drift <= band.u128()
+    }) {
+        return Err(ContractError::WithinTolerance {});
+    }
+
+    let mut messages = vec![];
+    let mut logs = vec![log("nav", nav)];
+
+    // sell the excess of every over-weight component
+    let mut proceeds = Uint128::zero();
+    for (component, balance, value, target) in positions.iter() {
+        if value <= target {
+            continue;
+        }
+        let amount = balance.multiply_ratio((*value - *target)?, *value);
+        if amount.is_zero() {
+            continue;
+        }
+
+        let offer_asset = Asset {
+            info: component.info.clone(),
+            amount,
+        };
+        let return_amount = simulate_component_swap(
+            deps,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            component,
+            &offer_asset,
+        )?
+        .return_amount;
+        let limit = find_slippage_limit(limits, &component.info);
+        let min_out = bounded_min_out(
+            deps,
+            env,
+            config,
+            component,
+            &offer_asset,
+            limit.and_then(|l| l.min_out),
+            max_deviation,
+        )?;
+        if let Some(min_out) = min_out {
+            if return_amount < min_out {
+                return Err(ContractError::SlippageExceeded {
+                    asset: component.info.to_string(),
+                    return_amount,
+                    min_out,
+                });
+            }
+        }
+        proceeds += return_amount.sub(sell_tax(deps, config, component, return_amount)?)?;
+
+        messages.push(sell_component_msg(
+            deps,
+            env,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            component,
+            amount,
+            limit.and_then(|l| l.max_spread).or(config.max_spread),
+        )?);
+        logs.push(log("sell", format!("{}:{}", component.info, amount)));
+    }
+
+    // and spread the proceeds over the under-weight ones
+    let shortfall = positions
+        .iter()
+        .filter(|(_, _, value, target)| value < target)
+        .try_fold(Uint128::zero(), |acc, (_, _, value, target)| {
+            Ok::<_, StdError>(acc + (*target - *value)?)
+        })?;
+    let bounty = std::cmp::min(
+        bounty,
+        proceeds.multiply_ratio(MAX_BOUNTY_SHARE, BASIS_POINTS),
+    );
+    let spend = std::cmp::min(proceeds.sub(bounty)?, shortfall);
+    for (component, _, value, target) in positions.iter() {
+        if value >= target {
+            continue;
+        }
+        let amount = ((*target - *value)?).multiply_ratio(spend, shortfall);
+        let amount = Asset {
+            info: AssetInfo::NativeToken {
+                denom: config.stable_denom.clone(),
+            },
+            amount,
+        }
+        .deduct_tax(deps)?
+        .amount;
+        if amount.is_zero() {
+            continue;
+        }
+
+        let offer_asset = Asset {
+            info: AssetInfo::NativeToken {
+                denom: config.stable_denom.clone(),
+            },
+            amount,
+        };
+        let limit = find_slippage_limit(limits, &component.info);
+        let min_out = bounded_min_out(
+            deps,
+            env,
+            config,
+            component,
+            &offer_asset,
+            limit.and_then(|l| l.min_out),
+            max_deviation,
+        )?;
+        if let Some(min_out) = min_out {
+            assert_min_out(
+                deps,
+                &terraswap_factory_raw,
+                &config.stable_denom,
+                component,
+                &offer_asset,
+                min_out,
+            )?;
+        }
+        messages.push(buy_component_msg(
+            deps,
+            env,
+            &terraswap_factory_raw,
+            &config.stable_denom,
+            component,
+            amount,
+            limit.and_then(|l| l.max_spread).or(config.max_spread),
+        )?);
+        logs.push(log("buy", format!("{}:{}", component.info, amount)));
+    }
+    messages.push(update_reserves_msg(deps, &env.contract.address, config)?);
+
+    Ok((
+        HandleResponse {
+            messages,
+            log: logs,
+            data: None,
+        },
+        bounty,
+    ))
+}
+
+/// The stricter of `min_out` and, with `max_deviation`, the return `offer_asset`
+/// fetches at the reference price of `component` less that share. Offers of the
+/// stable denom buy the component, anything else sells it.
+fn bounded_min_out<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+    component: &BasketComponent,
+    offer_asset: &Asset,
+    min_out: Option<Uint128>,
+    max_deviation: Option<Decimal256>,
+) -> Result<Option<Uint128>, ContractError> {
+    let max_deviation = match max_deviation {
+        Some(max_deviation) => max_deviation,
+        None => return Ok(min_out),
+    };
+
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+    let reference = query_reference_price(
+        deps,
+        &terraswap_factory,
+        config,
+        &component.info,
+        Some(env.block.time),
+    )?
+    .ok_or_else(|| ContractError::NoPriceHistory {
+        asset: component.info.to_string(),
+    })?;
+    if reference == Decimal256::zero() {
+        return Err(ContractError::PriceNotFound {
+            asset: component.info.to_string(),
+        });
+    }
+
+    let floor = Decimal256::one() - max_deviation;
+    let is_buy = offer_asset.info.equal(&AssetInfo::NativeToken {
+        denom: config.stable_denom.clone(),
+    });
+    let bound: Uint128 = if is_buy {
+        (Uint256::from(offer_asset.amount) * (floor / reference)).into()
+    } else {
+        (Uint256::from(offer_asset.amount) * reference * floor).into()
+    };
+
+    Ok(Some(std::cmp::max(
+        min_out.unwrap_or_else(Uint128::zero),
+        bound,
+    )))
+}
+
+/// Tax withheld from `return_amount` of the stable denom when selling `component`.
+/// Terraswap pays native returns out net of tax, market swaps are untaxed.
+fn sell_tax<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    component: &BasketComponent,
+    return_amount: Uint128,
+) -> StdResult<Uint128> {
+    match component.route {
+        SwapRoute::Market {} => Ok(Uint128::zero()),
+        SwapRoute::Terraswap {} => Asset {
+            info: AssetInfo::NativeToken {
+                denom: config.stable_denom.clone(),
+            },
+            amount: return_amount,
+        }
+        .compute_tax(deps),
+    }
+}
+
+/// Accrues the management fee since `last_fee_accrual` by growing the supply,
+/// returning the eTerra owed to the fee collector. The caller saves `state`.
+fn accrue_management_fee(env: &Env, config: &Config, state: &mut State) -> StdResult<Uint128> {
+    let elapsed = env.block.time.saturating_sub(state.last_fee_accrual);
+    let amount = state.total_supply.multiply_ratio(
+        u128::from(config.management_fee) * u128::from(elapsed),
+        BASIS_POINTS * u128::from(SECONDS_PER_YEAR),
+    );
+
+    state.total_supply += amount;
+    state.last_fee_accrual = env.block.time;
+    Ok(amount)
+}
+
+fn management_fee_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    amount: Uint128,
+) -> StdResult<Option<CosmosMsg<TerraMsgWrapper>>> {
+    if amount.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: deps.api.human_address(&config.eterra_contract)?,
+        send: vec![],
+        msg: to_binary(&Cw20HandleMsg::Mint {
+            recipient: deps.api.human_address(&config.fee_collector)?,
+            amount,
+        })?,
+    })))
+}
+
+/// Builds the message that swaps `amount` of the stable denom into `component`
+fn buy_component_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    component: &BasketComponent,
+    amount: Uint128,
+    max_spread: Option<Decimal>,
+) -> Result<CosmosMsg<TerraMsgWrapper>, ContractError> {
+    let offer_coin = Coin {
+        denom: stable_denom.to_string(),
+        amount,
+    };
+    match &component.route {
+        SwapRoute::Market {} => match &component.info {
+            AssetInfo::NativeToken { denom } => Ok(create_swap_msg(
+                env.contract.address.clone(),
+                offer_coin,
+                denom.clone(),
+            )),
+            AssetInfo::Token { .. } => Err(ContractError::MarketRouteNotNative {
+                asset: component.info.to_string(),
+            }),
+        },
+        SwapRoute::Terraswap {} => {
+            let pair_info =
+                query_component_pair(deps, terraswap_factory, stable_denom, &component.info)?;
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: pair_info.contract_addr,
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        amount,
+                        info: AssetInfo::NativeToken {
+                            denom: stable_denom.to_string(),
+                        },
+                    },
+                    max_spread,
+                    belief_price: None,
+                    to: None,
+                })?,
+                send: vec![offer_coin],
+            }))
+        }
+    }
+}
+
+/// Builds the message that swaps `amount` of `component` back into the stable denom
+fn sell_component_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    component: &BasketComponent,
+    amount: Uint128,
+    max_spread: Option<Decimal>,
+) -> Result<CosmosMsg<TerraMsgWrapper>, ContractError> {
+    match (&component.route, &component.info) {
+        (SwapRoute::Market {}, AssetInfo::NativeToken { denom }) => Ok(create_swap_msg(
+            env.contract.address.clone(),
+            Coin {
+                denom: denom.clone(),
+                amount,
+            },
+            stable_denom.to_string(),
+        )),
+        (SwapRoute::Market {}, AssetInfo::Token { .. }) => {
+            Err(ContractError::MarketRouteNotNative {
+                asset: component.info.to_string(),
+            })
+        }
+        (SwapRoute::Terraswap {}, AssetInfo::NativeToken { denom }) => {
+            let pair_info =
+                query_component_pair(deps, terraswap_factory, stable_denom, &component.info)?;
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: pair_info.contract_addr,
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        amount,
+                        info: component.info.clone(),
+                    },
+                    max_spread,
+                    belief_price: None,
+                    to: None,
+                })?,
+                send: vec![Coin {
+                    denom: denom.clone(),
+                    amount,
+                }],
+            }))
+        }
+        (SwapRoute::Terraswap {}, AssetInfo::Token { contract_addr }) => {
+            let pair_info =
+                query_component_pair(deps, terraswap_factory, stable_denom, &component.info)?;
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.clone(),
+                msg: to_binary(&Cw20HandleMsg::Send {
+                    contract: pair_info.contract_addr,
+                    amount,
+                    msg: Some(to_binary(&TerraswapCw20HookMsg::Swap {
+                        max_spread,
+                        belief_price: None,
+                        to: None,
+                    })?),
+                })?,
+                send: vec![],
+            }))
+        }
+    }
+}
+
+/// Self-call booking the swaps queued before it into the reserves
+fn update_reserves_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_addr: &HumanAddr,
+    config: &Config,
+) -> StdResult<CosmosMsg<TerraMsgWrapper>> {
+    let mut prev_balances = vec![];
+    for component in config.basket.iter() {
+        let info = component.info.to_normal(deps)?;
+        prev_balances.push(Asset {
+            amount: query_component_balance(deps, contract_addr, &info)?,
+            info,
+        });
+    }
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract_addr.clone(),
+        send: vec![],
+        msg: to_binary(&HandleMsg::UpdateReserves { prev_balances })?,
+    }))
+}
+
+/// Splits `deposit_amount` across the basket by target weight
+fn allocate_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    deposit_amount: Uint128,
+) -> StdResult<Vec<(BasketComponent, Uint128)>> {
+    config
+        .basket
+        .iter()
+        .map(|component| {
+            let component = component.to_normal(deps)?;
+            let amount = decimal_division(
+                deposit_amount * component.weight.into(),
+                Decimal::from_ratio(BASIS_POINTS, 1u128),
+            );
+            Ok((component, amount))
+        })
+        .collect()
+}
+
+/// The share of each component held by `contract_addr` redeemed by burning
+/// `burn_amount` out of `total_supply`, leaving out components with nothing to sell
+fn allocate_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    contract_addr: &HumanAddr,
+    burn_amount: Uint128,
+    total_supply: Uint128,
+) -> StdResult<Vec<(BasketComponent, Uint128)>> {
+    let mut allocation = vec![];
+    for component in config.basket.iter() {
+        let component = component.to_normal(deps)?;
+        let balance = query_component_balance(deps, contract_addr, &component.info)?;
+        let amount = balance.multiply_ratio(burn_amount, total_supply);
+        if !amount.is_zero() {
+            allocation.push((component, amount));
+        }
+    }
+
+    Ok(allocation)
+}
+
+/// eTerra minted for `invest_value` against a basket worth `nav` backing `total_supply`
+fn compute_mint_amount(
+    invest_value: Uint128,
+    total_supply: Uint128,
+    nav: Uint128,
+) -> Result<Uint128, ContractError> {
+    if total_supply.is_zero() {
+        Ok(invest_value)
+    } else if nav.is_zero() {
+        Err(ContractError::ZeroNav {})
+    } else {
+        Ok(invest_value.multiply_ratio(total_supply, nav))
+    }
+}
+
+/// Value at the valuation price of `return_amount` of `component` bought with the
+/// stable denom, net of the tax Terraswap withholds from native returns
+fn bought_value<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    config: &Config,
+    component: &BasketComponent,
+    return_amount: Uint128,
+    time: Option<u64>,
+) -> StdResult<Uint128> {
+    let tax = match (&component.route, &component.info) {
+        (SwapRoute::Terraswap {}, AssetInfo::NativeToken { .. }) => Asset {
+            info: component.info.clone(),
+            amount: return_amount,
+        }
+        .compute_tax(deps)?,
+        _ => Uint128::zero(),
+    };
+    query_value(
+        deps,
+        terraswap_factory,
+        config,
+        &component.info,
+        return_amount.sub(tax)?,
+        time,
+    )
+}
+
+fn assert_not_paused<S: Storage>(storage: &S, operation: Operation) -> Result<(), ContractError> {
+    if read_pause_flags(storage).load()?.is_paused(operation) {
+        return Err(ContractError::Paused {
+            operation: operation.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Refuses to trade when a component route prices it further from its reference
+/// than `max_price_deviation` allows, which is how a manipulated pool shows up.
+/// Burns go unchecked for components whose TWAP history is still refilling after
+/// the window was raised, so holders can leave; their slippage limits still hold.
+fn assert_prices_within_bound<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    time: u64,
+    operation: Operation,
+) -> Result<(), ContractError> {
+    if config.max_price_deviation == 0 {
+        return Ok(());
+    }
+
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+    let max_deviation = decimal_ratio(
+        Uint128::from(config.max_price_deviation),
+        Uint128(BASIS_POINTS),
+    );
+    for component in config.basket.iter() {
+        let component = component.to_normal(deps)?;
+        let reference = match query_reference_price(
+            deps,
+            &terraswap_factory,
+            config,
+            &component.info,
+            Some(time),
+        )? {
+            Some(reference) => reference,
+            None if operation == Operation::Burn => continue,
+            None => {
+                return Err(ContractError::NoPriceHistory {
+                    asset: component.info.to_string(),
+                })
+            }
+        };
+        let price = query_route_price(deps, &terraswap_factory, &config.stable_denom, &component)?;
+
+        let deviation = if price > reference {
+            price - reference
+        } else {
+            reference - price
+        };
+        if deviation > reference * max_deviation {
+            return Err(ContractError::PriceDeviationExceeded {
+                asset: component.info.to_string(),
+                price,
+                reference,
+                max_deviation: config.max_price_deviation,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn find_slippage_limit<'a>(
+    limits: &'a Option<Vec<SlippageLimit>>,
+    info: &AssetInfo,
+) -> Option<&'a SlippageLimit> {
+    limits
+        .as_ref()
+        .and_then(|limits| limits.iter().find(|l| l.info.equal(info)))
+}
+
+/// Rejects the swap when its simulated return is below `min_out`
+fn assert_min_out<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    component: &BasketComponent,
+    offer_asset: &Asset,
+    min_out: Uint128,
+) -> Result<(), ContractError> {
+    let return_amount = simulate_component_swap(
+        deps,
+        terraswap_factory,
+        stable_denom,
+        component,
+        offer_asset,
+    )?
+    .return_amount;
+    if return_amount < min_out {
+        return Err(ContractError::SlippageExceeded {
+            asset: component.info.to_string(),
+            return_amount,
+            min_out,
+        });
+    }
+
+    Ok(())
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::State {} => to_binary(&query_state(deps)?),
+        QueryMsg::FeeConfig {} => to_binary(&query_fee_config(deps)?),
+        QueryMsg::PauseFlags {} => to_binary(&query_pause_flags(deps)?),
+        QueryMsg::PendingConfig {} => to_binary(&query_pending_config(deps)?),
+        QueryMsg::SimulateMint { amount } => to_binary(&query_simulate_mint(deps, amount)?),
+        QueryMsg::SimulateBurn { amount } => to_binary(&query_simulate_burn(deps, amount)?),
+        QueryMsg::Nav {} => to_binary(&query_net_asset_value(deps)?),
+        QueryMsg::Prices {} => to_binary(&query_prices(deps)?),
+        QueryMsg::Twap {
+            asset,
+            window_seconds,
+        } => to_binary(&query_twap(deps, asset, window_seconds)?),
+    }
+}
+
+fn query_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ConfigResponse> {
+    let config = read_config(&deps.storage).load()?;
+    Ok(ConfigResponse {
+        terraswap_factory: deps.api.human_address(&config.terraswap_factory)?,
+        owner_addr: deps.api.human_address(&config.owner_addr)?,
+        guardian: config
+            .guardian
+            .map(|guardian| deps.api.human_address(&guardian))
+            .transpose()?,
+        eterra_contract: deps.api.human_address(&config.eterra_contract)?,
+        stable_denom: config.stable_denom,
+        basket: config
+            .basket
+            .iter()
+            .map(|c| c.to_normal(deps))
+            .collect::<StdResult<Vec<BasketComponent>>>()?,
+        max_spread: config.max_spread,
+        max_price_deviation: config.max_price_deviation,
+        twap_window: config.twap_window,
+        rebalance_tolerance: config.rebalance_tolerance,
+        rebalance_interval: config.rebalance_interval,
+        rebalance_bounty: config.rebalance_bounty,
+        config_timelock: config.config_timelock,
+    })
+}
+
+fn query_fee_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<FeeConfigResponse> {
+    let config = read_config(&deps.storage).load()?;
+    Ok(FeeConfigResponse {
+        mint_fee: config.mint_fee,
+        burn_fee: config.burn_fee,
+        management_fee: config.management_fee,
+        fee_collector: deps.api.human_address(&config.fee_collector)?,
+    })
+}
+
+fn query_twap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset: AssetInfo,
+    window_seconds: u64,
+) -> StdResult<TwapResponse> {
+    let info_raw = asset.to_raw(deps)?;
+    let history = read_price_history(&deps.storage).load()?;
+    let component = history
+        .iter()
+        .find(|h| h.info.equal(&info_raw))
+        .ok_or_else(|| ContractError::NotBasketComponent {
+            asset: asset.to_string(),
+        })?;
+
+    let end_time = match component.observations.last() {
+        Some(observation) => observation.timestamp,
+        None => {
+            return Err(ContractError::NoPriceHistory {
+                asset: asset.to_string(),
+            }
+            .into())
+        }
+    };
+    let (price, start_time) =
+        component
+            .twap(end_time, window_seconds)
+            .ok_or_else(|| ContractError::NoPriceHistory {
+                asset: asset.to_string(),
+            })?;
+
+    Ok(TwapResponse {
+        info: asset,
+        price,
+        start_time,
+        end_time,
+    })
+}
+
+fn query_prices<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PricesResponse> {
+    let config = read_config(&deps.storage).load()?;
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+
+    let mut prices = vec![];
+    for component in config.basket.iter() {
+        let info = component.info.to_normal(deps)?;
+        prices.push(PriceResponse {
+            price: query_price(deps, &terraswap_factory, &config.stable_denom, &info)?,
+            info,
+        });
+    }
+
+    Ok(PricesResponse { prices })
+}
+
+fn query_net_asset_value<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<NavResponse> {
+    let config = read_config(&deps.storage).load()?;
+    let state = read_state(&deps.storage).load()?;
+    let contract_addr = deps.api.human_address(&config.contract_addr)?;
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+
+    let mut nav = Uint128::zero();
+    let mut components = vec![];
+    for component in config.basket.iter() {
+        let component = component.to_normal(deps)?;
+        let balance = query_component_balance(deps, &contract_addr, &component.info)?;
+        let price =
+            query_valuation_price(deps, &terraswap_factory, &config, &component.info, None)?;
+        let value: Uint128 = (Uint256::from(balance) * price).into();
+        nav += value;
+        components.push(ComponentNavResponse {
+            info: component.info,
+            balance,
+            price,
+            value,
+            weight: Decimal256::zero(),
+            target_weight: component.weight,
+        });
+    }
+
+    if !nav.is_zero() {
+        for component in components.iter_mut() {
+            component.weight =
+                decimal_ratio(component.value, nav) * Decimal256::from_uint256(BASIS_POINTS);
+        }
+    }
+
+    let price = if state.total_supply.is_zero() {
+        Decimal256::one()
+    } else {
+        decimal_ratio(nav, state.total_supply)
+    };
+
+    Ok(NavResponse {
+        nav,
+        total_supply: state.total_supply,
+        price,
+        components,
+    })
+}
+
+fn query_simulate_mint<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    amount: Uint128,
+) -> StdResult<SimulateMintResponse> {
+    let config = read_config(&deps.storage).load()?;
+    let state = read_state(&deps.storage).load()?;
+    let contract_addr = deps.api.human_address(&config.contract_addr)?;
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+    let stable_info = AssetInfo::NativeToken {
+        denom: config.stable_denom.clone(),
+    };
+
+    let fee_amount = amount.multiply_ratio(config.mint_fee, BASIS_POINTS);
+    let deposit_amount = amount.sub(fee_amount)?;
+    let mut tax_amount = Asset {
+        info: stable_info.clone(),
+        amount: fee_amount,
+    }
+    .compute_tax(deps)?;
+
+    let mut invest_value = Uint128::zero();
+    let mut swaps = vec![];
+    for (component, amount) in allocate_deposit(deps, &config, deposit_amount)? {
+        let tax = Asset {
+            info: stable_info.clone(),
+            amount,
+        }
+        .compute_tax(deps)?;
+        tax_amount += tax;
+        let amount = amount.sub(tax)?;
+        let simulation = simulate_component_swap(
+            deps,
+            &terraswap_factory,
+            &config.stable_denom,
+            &component,
+            &Asset {
+                info: stable_info.clone(),
+                amount,
+            },
+        )?;
+        invest_value += bought_value(
+            deps,
+            &terraswap_factory,
+            &config,
+            &component,
+            simulation.return_amount,
+            None,
+        )?;
+        swaps.push(swap_rate(component.info, amount, simulation, tax));
+    }
+
+    let nav = query_nav(deps, &contract_addr, &config, None)?;
+    Ok(SimulateMintResponse {
+        mint_amount: compute_mint_amount(invest_value, state.total_supply, nav)?,
+        tax_amount,
+        fee_amount,
+        swaps,
+    })
+}
+
+fn query_simulate_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    amount: Uint128,
+) -> StdResult<SimulateBurnResponse> {
+    let config = read_config(&deps.storage).load()?;
+    let state = read_state(&deps.storage).load()?;
+    if amount > state.total_supply {
+        return Err(ContractError::BurnExceedsSupply {
+            amount,
+            total_supply: state.total_supply,
+        }
+        .into());
+    }
+    let contract_addr = deps.api.human_address(&config.contract_addr)?;
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+
+    let mut proceeds = Uint128::zero();
+    let mut tax_amount = Uint128::zero();
+    let mut swaps = vec![];
+    for (component, amount) in
+        allocate_burn(deps, &config, &contract_addr, amount, state.total_supply)?
+    {
+        let simulation = simulate_component_swap(
+            deps,
+            &terraswap_factory,
+            &config.stable_denom,
+            &component,
+            &Asset {
+                info: component.info.clone(),
+                amount,
+            },
+        )?;
+        let tax = sell_tax(deps, &config, &component, simulation.return_amount)?;
+        proceeds += simulation.return_amount.sub(tax)?;
+        tax_amount += tax;
+        swaps.push(swap_rate(component.info, amount, simulation, tax));
+    }
+
+    // the payout is taxed again when it is sent
+    let fee_amount = proceeds.multiply_ratio(config.burn_fee, BASIS_POINTS);
+    let return_amount = Asset {
+        info: AssetInfo::NativeToken {
+            denom: config.stable_denom.clone(),
+        },
+        amount: proceeds.sub(fee_amount)?,
+    }
+    .deduct_tax(deps)?
+    .amount;
+    tax_amount += proceeds.sub(fee_amount)?.sub(return_amount)?;
+    Ok(SimulateBurnResponse {
+        return_amount,
+        tax_amount,
+        fee_amount,
+        swaps,
+    })
+}
+
+fn swap_rate(
+    info: AssetInfo,
+    offer_amount: Uint128,
+    simulation: SimulationResponse,
+    tax_amount: Uint128,
+) -> SwapRateResponse {
+    let to_decimal = |amount: Uint128| Decimal256::from_uint256(Uint256::from(amount));
+    SwapRateResponse {
+        info,
+        offer_amount,
+        return_amount: to_decimal(simulation.return_amount),
+        spread_amount: to_decimal(simulation.spread_amount),
+        commission_amount: to_decimal(simulation.commission_amount),
+        tax_amount: to_decimal(tax_amount),
+    }
+}
+
+fn query_pending_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Option<PendingConfigResponse>> {
+    let pending = read_pending_config(&deps.storage).may_load()?;
+    Ok(pending.map(|pending| PendingConfigResponse {
+        update: pending.update,
+        execute_after: pending.execute_after,
+    }))
+}
+
+fn query_pause_flags<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PauseFlagsResponse> {
+    let flags = read_pause_flags(&deps.storage).load()?;
+    Ok(PauseFlagsResponse {
+        mint: flags.mint,
+        burn: flags.burn,
+        rebalance: flags.rebalance,
+    })
+}
+
+fn query_state<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<StateResponse> {
+    let state = read_state(&deps.storage).load()?;
+    Ok(StateResponse {
+        total_supply: state.total_supply,
+        reserves: state
+            .reserves
+            .iter()
+            .map(|r| {
+                Ok(ReserveResponse {
+                    info: r.info.to_normal(deps)?,
+                    amount: r.amount,
+                })
+            })
+            .collect::<StdResult<Vec<ReserveResponse>>>()?,
+        last_fee_accrual: state.last_fee_accrual,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_querier::mock_dependencies;
+
+    use super::*;
+    use crate::msg::ComponentWeight;
+    use cosmwasm_std::{
+        coins,
+        testing::{mock_env, MOCK_CONTRACT_ADDR},
+        HumanAddr,
+    };
+    use terra_cosmwasm::{TerraMsg, TerraRoute};
+
+    fn default_basket() -> Vec<BasketComponent> {
+        vec![
+            BasketComponent {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                weight: Decimal256::from_uint256(2500u128),
+                route: SwapRoute::Terraswap {},
+            },
+            BasketComponent {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("MIRROR"),
+                },
+                weight: Decimal256::from_uint256(2500u128),
+                route: SwapRoute::Terraswap {},
+            },
+            BasketComponent {
+                info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                weight: Decimal256::from_uint256(5000u128),
+                route: SwapRoute::Market {},
+            },
+        ]
+    }
+    fn mock_pool(
+        stable_denom: &str,
+        token: &str,
+        stable_amount: u128,
+        token_amount: u128,
+    ) -> [Asset; 2] {
+        [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: stable_denom.to_string(),
+                },
+                amount: Uint128(stable_amount),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from(token),
+                },
+                amount: Uint128(token_amount),
+            },
+        ]
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id: 123u64,
+                send: vec![],
+                label: None,
+                msg: to_binary(&TokenInitMsg {
+                    name: "Edge Terra USD".to_string(),
+                    symbol: "eTerra".to_string(),
+                    decimals: 6u8,
+                    initial_balances: vec![Cw20CoinHuman {
+                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        amount: Uint128(0),
+                    }],
+                    mint: Some(MinterResponse {
+                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        cap: None,
+                    }),
+                    init_hook: Some(InitHook {
+                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
+                    })
+                })
+                .unwrap(),
+            })]
+        );
+
+        // Register edge token contract
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // Cannot register again
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        let config = query_config(&deps).unwrap();
+        assert_eq!("terraswapfactory", config.terraswap_factory.as_str());
+        assert_eq!("uusd", config.stable_denom.as_str());
+        assert_eq!(default_basket(), config.basket);
+
+        let state = query_state(&deps).unwrap();
+        assert_eq!(3, state.reserves.len());
+        assert_eq!(
+            AssetInfo::NativeToken {
+                denom: "uluna".to_string()
+            },
+            state.reserves[2].info
+        );
+    }
+
+    #[test]
+    fn init_validation() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &[]);
+        let init_msg = |basket: Vec<BasketComponent>, stable_denom: &str| InitMsg {
+            basket,
+            guardian: None,
+            stable_denom: stable_denom.to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+
+        // stable denom is sliced for the token name
+        for denom in &["", "u", "USD", "uUSD", "usd$"] {
+            let res = init(&mut deps, env.clone(), init_msg(default_basket(), denom));
+            match res {
+                Err(StdError::GenericErr { msg, .. }) => {
+                    assert!(msg.starts_with("invalid_stable_denom: "))
+                }
+                _ => panic!("DO NOT ENTER HERE"),
+            }
+        }
+
+        let _res = init(&mut deps, env.clone(), init_msg(vec![], "uusd")).unwrap_err();
+
+        let mut basket = default_basket();
+        basket[2].weight = Decimal256::from_uint256(4000u128);
+        let res = init(&mut deps, env.clone(), init_msg(basket, "uusd"));
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(
+                    msg,
+                    "allocation_sum_invalid: Weights must sum to 10000 basis points, got 9000"
+                )
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let mut basket = default_basket();
+        basket[0].weight = Decimal256::zero();
+        basket[1].weight = Decimal256::from_uint256(5000u128);
+        let _res = init(&mut deps, env.clone(), init_msg(basket, "uusd")).unwrap_err();
+
+        let mut basket = default_basket();
+        basket[1].info = basket[0].info.clone();
+        let _res = init(&mut deps, env.clone(), init_msg(basket, "uusd")).unwrap_err();
+
+        let mut basket = default_basket();
+        basket[0].route = SwapRoute::Market {};
+        let _res = init(&mut deps, env.clone(), init_msg(basket, "uusd")).unwrap_err();
+
+        let _res = init(&mut deps, env, init_msg(default_basket(), "ukrw")).unwrap();
+    }
+    #[test]
+    fn mint() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000000000u128, 1000000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000000u128, 1000000000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[(
+            &"uusd".to_string(),
+            &"uluna".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_tax(
+            Decimal::zero(),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id: 123u64,
+                send: vec![],
+                label: None,
+                msg: to_binary(&TokenInitMsg {
+                    name: "Edge Terra USD".to_string(),
+                    symbol: "eTerra".to_string(),
+                    decimals: 6u8,
+                    initial_balances: vec![Cw20CoinHuman {
+                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        amount: Uint128(0),
+                    }],
+                    mint: Some(MinterResponse {
+                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        cap: None,
+                    }),
+                    init_hook: Some(InitHook {
+                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
+                    })
+                })
+                .unwrap(),
+            })]
+        );
+
+        // Register edge token contract
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // Cannot register again
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        let msg = HandleMsg::Mint { limits: None };
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        let _balance = deps
+            .querier
+            .query_balance(MOCK_CONTRACT_ADDR, "uusd")
+            .unwrap()
+            .amount;
+
+        assert_eq!(Uint128(0u128), _balance);
+        assert_eq!(res.messages.len(), 5);
+        // the first mint is priced at what the swaps bring in, less their price impact
+        assert_eq!(
+            res.log,
+            vec![
+                log("mint", "99998"),
+                log("nav", "0"),
+                log("fee", "0"),
+                log("management_fee", "0")
+            ]
+        );
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairANC"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        },
+                        amount: Uint128(25000u128), // 1000 * 25%
+                    },
+                    max_spread: None,
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(25000u128),
+                    denom: "uusd".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            res.messages[1],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairMIRROR"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        },
+                        amount: Uint128(25000u128), // 1000 * 25$
+                    },
+                    max_spread: None,
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(25000u128),
+                    denom: "uusd".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            res.messages[2],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(50000u128),
+                        denom: "uusd".to_string()
+                    },
+                    ask_denom: "uluna".to_string()
+                }
+            })
+        );
+        assert_eq!(
+            res.messages[4],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ETerra"),
+                send: vec![],
+                msg: to_binary(&Cw20HandleMsg::Mint {
+                    recipient: HumanAddr::from("alice"),
+                    amount: Uint128(99998u128),
+                })
+                .unwrap(),
+            })
+        );
+
+        // each transfer pays the capped tax on its own
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(100u128))],
+        );
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::zero();
+        store_state(&mut deps.storage).save(&state).unwrap();
+        let msg = HandleMsg::Mint { limits: None };
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairANC"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        },
+                        amount: Uint128(24900u128),
+                    },
+                    max_spread: None,
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(24900u128),
+                    denom: "uusd".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            res.messages[2],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(49900u128),
+                        denom: "uusd".to_string()
+                    },
+                    ask_denom: "uluna".to_string()
+                }
+            })
+        );
+    }
+    #[test]
+    fn mint_priced_against_nav() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(10000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(10000u128))],
+            ),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 2000000000000u128, 1000000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000000u128, 1000000000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[
+            (
+                &"uluna".to_string(),
+                &"uusd".to_string(),
+                &Decimal::from_ratio(20u128, 1u128),
+            ),
+            (
+                &"uusd".to_string(),
+                &"uluna".to_string(),
+                &Decimal::from_ratio(1u128, 20u128),
+            ),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(20u128, 1u128),
+        )]);
+
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        // 25000 eTerra outstanding against 20000 + 10000 + 20000 uusd of holdings
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(25000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        let msg = HandleMsg::Mint { limits: None };
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        // the swaps bring in 24998 + 24999 + 50000 uusd worth after price impact
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("mint", "49998"),
+                log("nav", "50000"),
+                log("fee", "0"),
+                log("management_fee", "0")
+            ]
+        );
+        assert_eq!(
+            res.messages[4],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ETerra"),
+                send: vec![],
+                msg: to_binary(&Cw20HandleMsg::Mint {
+                    recipient: HumanAddr::from("alice"),
+                    amount: Uint128(49998u128),
+                })
+                .unwrap(),
+            })
+        );
+
+        let state: State = read_state(&deps.storage).load().unwrap();
+        assert_eq!(Uint128(74998u128), state.total_supply);
+    }
+    #[test]
+    fn mint_with_slippage_limits() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: Some(Decimal::percent(1)),
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000000u128, 1000000000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[(
+            &"uusd".to_string(),
+            &"uluna".to_string(),
+            &Decimal::percent(1),
+        )]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::percent(10000),
+        )]);
+
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+
+        // 25000 uusd into a 1000000/1000000 pool only returns 24390 ANC
+        let msg = HandleMsg::Mint {
+            limits: Some(vec![SlippageLimit {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                max_spread: None,
+                min_out: Some(Uint128(25000u128)),
+            }]),
+        };
+        let res = handle(&mut deps, env.clone(), msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "slippage_exceeded: Slippage exceeded for ANC: simulated return 24390 is below minimum 25000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // 50000 uusd through the market only returns 500 uluna
+        let msg = HandleMsg::Mint {
+            limits: Some(vec![SlippageLimit {
+                info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                max_spread: None,
+                min_out: Some(Uint128(600u128)),
+            }]),
+        };
+        let res = handle(&mut deps, env.clone(), msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "slippage_exceeded: Slippage exceeded for uluna: simulated return 500 is below minimum 600"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let msg = HandleMsg::Mint {
+            limits: Some(vec![
+                SlippageLimit {
+                    info: AssetInfo::Token {
+                        contract_addr: HumanAddr::from("ANC"),
+                    },
+                    max_spread: Some(Decimal::percent(5)),
+                    min_out: Some(Uint128(24000u128)),
+                },
+                SlippageLimit {
+                    info: AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                    max_spread: None,
+                    min_out: Some(Uint128(500u128)),
+                },
+            ]),
+        };
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairANC"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        },
+                        amount: Uint128(25000u128),
+                    },
+                    max_spread: Some(Decimal::percent(5)),
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(25000u128),
+                    denom: "uusd".to_string(),
+                }],
+            })
+        );
+        // falls back to the configured default
+        assert_eq!(
+            res.messages[1],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairMIRROR"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        },
+                        amount: Uint128(25000u128),
+                    },
+                    max_spread: Some(Decimal::percent(1)),
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(25000u128),
+                    denom: "uusd".to_string(),
+                }],
+            })
+        );
+    }
+    #[test]
+    fn burn() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(3000000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(400000u128))],
+            ),
+        ]);
+
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id: 123u64,
+                send: vec![],
+                label: None,
+                msg: to_binary(&TokenInitMsg {
+                    name: "Edge Terra USD".to_string(),
+                    symbol: "eTerra".to_string(),
+                    decimals: 6u8,
+                    initial_balances: vec![Cw20CoinHuman {
+                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        amount: Uint128(0),
+                    }],
+                    mint: Some(MinterResponse {
+                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        cap: None,
+                    }),
+                    init_hook: Some(InitHook {
+                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
+                    })
+                })
+                .unwrap(),
+            })]
+        );
+
+        // Register edge token contract
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // Cannot register again
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(20000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(5000u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+
+        // only eTerra can be redeemed
+        let env = mock_env("ANC", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+
+        let env = mock_env("ETerra", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+
+        // a quarter of the supply redeems a quarter of each component balance
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ANC"),
+                msg: to_binary(&Cw20HandleMsg::Send {
+                    contract: HumanAddr::from("pairANC"),
+                    amount: Uint128(250000u128),
+                    msg: Some(
+                        to_binary(&TerraswapCw20HookMsg::Swap {
+                            max_spread: None,
+                            belief_price: None,
+                            to: None,
+                        })
+                        .unwrap()
+                    ),
+                })
+                .unwrap(),
+                send: vec![],
+            })
+        );
+        assert_eq!(
+            res.messages[1],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("MIRROR"),
+                msg: to_binary(&Cw20HandleMsg::Send {
+                    contract: HumanAddr::from("pairMIRROR"),
+                    amount: Uint128(100000u128),
+                    msg: Some(
+                        to_binary(&TerraswapCw20HookMsg::Swap {
+                            max_spread: None,
+                            belief_price: None,
+                            to: None,
+                        })
+                        .unwrap()
+                    ),
+                })
+                .unwrap(),
+                send: vec![],
+            })
+        );
+        assert_eq!(
+            res.messages[2],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(750000u128),
+                        denom: "uluna".to_string()
+                    },
+                    ask_denom: "uusd".to_string()
+                }
+            })
+        );
+        // the sales are booked into the reserves once they have settled
+        assert_eq!(
+            res.messages[3],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                send: vec![],
+                msg: to_binary(&HandleMsg::UpdateReserves {
+                    prev_balances: vec![
+                        Asset {
+                            info: AssetInfo::Token {
+                                contract_addr: HumanAddr::from("ANC"),
+                            },
+                            amount: Uint128(1000000u128),
+                        },
+                        Asset {
+                            info: AssetInfo::Token {
+                                contract_addr: HumanAddr::from("MIRROR"),
+                            },
+                            amount: Uint128(400000u128),
+                        },
+                        Asset {
+                            info: AssetInfo::NativeToken {
+                                denom: "uluna".to_string(),
+                            },
+                            amount: Uint128(3000000u128),
+                        },
+                    ],
+                })
+                .unwrap(),
+            })
+        );
+        assert_eq!(
+            res.messages[4],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ETerra"),
+                send: vec![],
+                msg: to_binary(&Cw20HandleMsg::Burn {
+                    amount: Uint128(5000u128),
+                })
+                .unwrap(),
+            })
+        );
+        assert_eq!(
+            res.messages[5],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                send: vec![],
+                msg: to_binary(&HandleMsg::RedeemToken {
+                    sender: HumanAddr::from("alice"),
+                    prev_balance: Uint128::zero(),
+                })
+                .unwrap()
+            })
+        );
+
+        let state: State = read_state(&deps.storage).load().unwrap();
+        assert_eq!(Uint128(15000u128), state.total_supply);
+
+        // cannot burn more than the outstanding supply
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(20000u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+    }
+    #[test]
+    fn update_config() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        let weights = vec![
+            ComponentWeight {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                weight: Decimal256::from_uint256(4000u128),
+            },
+            ComponentWeight {
+                info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                weight: Decimal256::from_uint256(3500u128),
+            },
+        ];
+
+        // only the owner can update
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            weights: Some(weights.clone()),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        // weights must sum to 10000
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            weights: Some(vec![weights[0].clone()]),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        // unknown components are rejected
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            weights: Some(vec![ComponentWeight {
+                info: AssetInfo::NativeToken {
+                    denom: "ukrw".to_string(),
+                },
+                weight: Decimal256::from_uint256(10000u128),
+            }]),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            terraswap_factory: Some(HumanAddr::from("newfactory")),
+            weights: Some(weights),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("terraswap_factory", "newfactory"),
+                log("weight", "ANC:4000"),
+                log("weight", "uluna:3500"),
+            ]
+        );
+
+        let config = query_config(&deps).unwrap();
+        assert_eq!("newfactory", config.terraswap_factory.as_str());
+        assert_eq!(Decimal256::from_uint256(4000u128), config.basket[0].weight);
+        assert_eq!(Decimal256::from_uint256(2500u128), config.basket[1].weight);
+        assert_eq!(Decimal256::from_uint256(3500u128), config.basket[2].weight);
+    }
+    #[test]
+    fn ownership_transfer() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        // nothing to accept or cancel yet
+        let env = mock_env("newowner", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::AcceptOwnership {}).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::CancelOwnershipProposal {}).unwrap_err();
+
+        // only the owner can propose
+        let msg = HandleMsg::ProposeNewOwner {
+            owner: HumanAddr::from("newowner"),
+            expires_in_blocks: 100,
+        };
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+
+        // an expiry past the end of the chain is refused rather than overflowing
+        let env = mock_env("creator", &[]);
+        let res = handle(
+            &mut deps,
+            env,
+            HandleMsg::ProposeNewOwner {
+                owner: HumanAddr::from("newowner"),
+                expires_in_blocks: u64::MAX,
+            },
+        );
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                format!(
+                    "invalid_expiry: Expiry of {} blocks overflows the block height",
+                    u64::MAX
+                )
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("pending_owner", "newowner"),
+                log("expires_at", (env.block.height + 100).to_string()),
+            ]
+        );
+
+        // the proposal can be cancelled by the owner
+        let _res = handle(&mut deps, env, HandleMsg::CancelOwnershipProposal {}).unwrap();
+        let env = mock_env("newowner", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::AcceptOwnership {}).unwrap_err();
+
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // only the nominee can accept
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::AcceptOwnership {}).unwrap_err();
+
+        // not after the proposal expired
+        let mut env = mock_env("newowner", &[]);
+        env.block.height += 101;
+        let _res = handle(&mut deps, env, HandleMsg::AcceptOwnership {}).unwrap_err();
+
+        let env = mock_env("newowner", &[]);
+        let res = handle(&mut deps, env, HandleMsg::AcceptOwnership {}).unwrap();
+        assert_eq!(res.log, vec![log("owner", "newowner")]);
+
+        let config = query_config(&deps).unwrap();
+        assert_eq!("newowner", config.owner_addr.as_str());
+
+        // previous owner lost its rights
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            terraswap_factory: Some(HumanAddr::from("newfactory")),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+    }
+    #[test]
+    fn redeem_token() {
+        let mut deps = mock_dependencies(
+            20,
+            &[
+                Coin {
+                    denom: "uluna".to_string(),
+                    amount: Uint128(1000000u128),
+                },
+                Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(10000u128),
+                },
+            ],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
 
         // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
+        let _res = init(&mut deps, env, msg).unwrap();
+        // Register edge token contract
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128(4000u128),
+        };
+        let env = mock_env("alice", &[]);
+        // invalid sender
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        // only the balance gained since the snapshot is paid out
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(6000u128, "uusd"),
+            })]
+        );
+        assert_eq!(res.log, vec![log("redeem", "6000"), log("fee", "0")]);
+
+        // nothing gained, nothing sent
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128(10000u128),
+        };
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.messages, vec![]);
+        assert_eq!(res.log, vec![log("redeem", "0"), log("fee", "0")]);
+
+        // the tax on the payout comes out of it, not out of the rest of the fund:
+        // 5940 sent costs the 6000 gained
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128(4000u128),
+        };
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(5940u128, "uusd"),
+            })]
+        );
+    }
+    #[test]
+    fn non_usd_stable_denom() {
+        let mut deps = mock_dependencies(
+            20,
+            &[
+                Coin {
+                    denom: "uluna".to_string(),
+                    amount: Uint128(2000000u128),
+                },
+                Coin {
+                    denom: "ukrw".to_string(),
+                    amount: Uint128(30000u128),
+                },
+                Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(10000u128),
+                },
+            ],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "ukrw".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"ukrwANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"ukrwMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("ukrw", "ANC", 1000000000000u128, 1000000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("ukrw", "MIRROR", 1000000000000u128, 1000000000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[(
+            &"ukrw".to_string(),
+            &"uluna".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"ukrw".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_tax(
+            Decimal::zero(),
+            &[(&"ukrw".to_string(), &Uint128(1000000u128))],
+        );
+
+        let _res = init(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // a uusd deposit is not accepted
+        let msg = HandleMsg::Mint { limits: None };
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(
+                    msg,
+                    "zero_deposit: Deposit amount must be greater than 0 ukrw"
+                )
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "ukrw".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pairANC"),
+                msg: to_binary(&TerraswapHandleMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "ukrw".to_string()
+                        },
+                        amount: Uint128(25000u128),
+                    },
+                    max_spread: None,
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+                send: vec![Coin {
+                    amount: Uint128(25000u128),
+                    denom: "ukrw".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            res.messages[2],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(50000u128),
+                        denom: "ukrw".to_string()
+                    },
+                    ask_denom: "uluna".to_string()
+                }
+            })
+        );
+
+        // burning half of the 99998 minted sells components back into ukrw and
+        // snapshots the ukrw balance
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(49999u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+        let env = mock_env("ETerra", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages.last(),
+            Some(&CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                send: vec![],
+                msg: to_binary(&HandleMsg::RedeemToken {
+                    sender: HumanAddr::from("alice"),
+                    prev_balance: Uint128(30000u128),
+                })
+                .unwrap()
+            }))
+        );
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(1000000u128),
+                        denom: "uluna".to_string()
+                    },
+                    ask_denom: "ukrw".to_string()
+                }
+            })
+        );
+
+        // only the ukrw gained is paid out, the uusd balance stays put
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128(10000u128),
+        };
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("alice"),
+                amount: coins(20000u128, "ukrw"),
+            })]
+        );
+        assert_eq!(res.log, vec![log("redeem", "20000"), log("fee", "0")]);
+    }
+    #[test]
+    fn fees() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(10000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 100,
+            burn_fee: 50,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("treasury"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000000000u128, 1000000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000000u128, 1000000000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[(
+            &"uusd".to_string(),
+            &"uluna".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::one(),
+        )]);
+        deps.querier.with_tax(
+            Decimal::zero(),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+
+        // fees above 100% are rejected
+        let mut invalid_msg = msg.clone();
+        invalid_msg.burn_fee = 10001;
+        let res = init(&mut deps, env.clone(), invalid_msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "invalid_fee: Fee of 10001 basis points must not exceed 10000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let _res = init(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let res = query(&deps, QueryMsg::FeeConfig {}).unwrap();
+        let fee_config: FeeConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            fee_config,
+            FeeConfigResponse {
+                mint_fee: 100,
+                burn_fee: 50,
+                management_fee: 0,
+                fee_collector: HumanAddr::from("treasury"),
+            }
+        );
+
+        // 1% of the deposit goes to the collector, the rest is allocated
+        let msg = HandleMsg::Mint { limits: None };
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.messages.len(), 6);
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("treasury"),
+                amount: coins(1000u128, "uusd"),
+            })
+        );
+        assert_eq!(
+            res.messages[3],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from("cosmos2contract"),
+                    offer_coin: Coin {
+                        amount: Uint128(49500u128),
+                        denom: "uusd".to_string()
+                    },
+                    ask_denom: "uluna".to_string()
+                }
+            })
+        );
+        assert_eq!(
+            res.log,
+            vec![
+                log("mint", "98998"),
+                log("nav", "0"),
+                log("fee", "1000"),
+                log("management_fee", "0")
+            ]
+        );
+
+        // 0.5% of the redemption goes to the collector
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128::zero(),
+        };
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("treasury"),
+                    amount: coins(50u128, "uusd"),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("alice"),
+                    amount: coins(9950u128, "uusd"),
+                })
+            ]
+        );
+        assert_eq!(res.log, vec![log("redeem", "9950"), log("fee", "50")]);
+
+        // only the owner can change fees
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            mint_fee: Some(0),
+            burn_fee: Some(50),
+            fee_collector: Some(HumanAddr::from("newtreasury")),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap();
+        assert_eq!(
+            res.log,
+            vec![log("mint_fee", "0"), log("fee_collector", "newtreasury")]
+        );
+
+        let res = query(&deps, QueryMsg::FeeConfig {}).unwrap();
+        let fee_config: FeeConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(fee_config.mint_fee, 0);
+        assert_eq!(fee_config.fee_collector, HumanAddr::from("newtreasury"));
+    }
+    #[test]
+    fn management_fee() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 200,
+            fee_collector: HumanAddr::from("treasury"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let start = env.block.time;
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        // nothing to collect before eTerra exists
+        let env = mock_env("anyone", &[]);
+        let res = handle(&mut deps, env, HandleMsg::CollectFees {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "not_registered: eTerra token is not registered yet")
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(1000000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        // half a year at 2% p.a. mints 1% of the supply to the collector
+        let mut env = mock_env("anyone", &[]);
+        env.block.time = start + SECONDS_PER_YEAR / 2;
+        let res = handle(&mut deps, env.clone(), HandleMsg::CollectFees {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ETerra"),
+                send: vec![],
+                msg: to_binary(&Cw20HandleMsg::Mint {
+                    recipient: HumanAddr::from("treasury"),
+                    amount: Uint128(10000u128),
+                })
+                .unwrap(),
+            })]
+        );
+        assert_eq!(res.log, vec![log("management_fee", "10000")]);
+
+        let res = query(&deps, QueryMsg::State {}).unwrap();
+        let state: StateResponse = from_binary(&res).unwrap();
+        assert_eq!(state.total_supply, Uint128(1010000u128));
+        assert_eq!(state.last_fee_accrual, start + SECONDS_PER_YEAR / 2);
+
+        // collecting again in the same block accrues nothing
+        let res = handle(&mut deps, env.clone(), HandleMsg::CollectFees {}).unwrap();
+        assert_eq!(res.messages, vec![]);
+        assert_eq!(res.log, vec![log("management_fee", "0")]);
+
+        // changing the rate settles what accrued at the old one first
+        env.block.time += SECONDS_PER_YEAR / 2;
+        env.message.sender = HumanAddr::from("creator");
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            management_fee: Some(100),
+            ..ConfigUpdate::default()
+        });
+        let _res = handle(&mut deps, env.clone(), msg).unwrap();
+        let res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("ETerra"),
+                send: vec![],
+                msg: to_binary(&Cw20HandleMsg::Mint {
+                    recipient: HumanAddr::from("treasury"),
+                    amount: Uint128(10100u128),
+                })
+                .unwrap(),
+            })]
+        );
+        assert_eq!(res.log, vec![log("management_fee", "100")]);
+        let state: State = read_state(&deps.storage).load().unwrap();
+        assert_eq!(state.total_supply, Uint128(1020100u128));
+    }
+    #[test]
+    fn rebalance() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1000000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 500,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        let uusd = AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        };
+        let anc = AssetInfo::Token {
+            contract_addr: HumanAddr::from("ANC"),
+        };
+        let mirror = AssetInfo::Token {
+            contract_addr: HumanAddr::from("MIRROR"),
+        };
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &[
+                    Asset {
+                        info: uusd.clone(),
+                        amount: Uint128(2000000u128),
+                    },
+                    Asset {
+                        info: anc,
+                        amount: Uint128(1000000u128),
+                    },
+                ],
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &[
+                    Asset {
+                        info: uusd,
+                        amount: Uint128(1000000u128),
+                    },
+                    Asset {
+                        info: mirror,
+                        amount: Uint128(1000000u128),
+                    },
+                ],
+            ),
+        ]);
+        deps.querier.with_tax(
+            Decimal::zero(),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+
+        // 1000000 + 1000000 + 2000000 uusd is right on target
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(500000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+        ]);
+        deps.querier.with_market_rates(&[
+            (
+                &"uluna".to_string(),
+                &"uusd".to_string(),
+                &Decimal::from_ratio(2u128, 1u128),
+            ),
+            (
+                &"uusd".to_string(),
+                &"uluna".to_string(),
+                &Decimal::from_ratio(1u128, 2u128),
+            ),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(2u128, 1u128),
+        )]);
+
+        let msg = HandleMsg::Rebalance { limits: None };
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "within_tolerance: Basket weights are within the rebalance tolerance"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // ANC doubles to 2000000 uusd while luna halves to 1000000 uusd
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+        ]);
+        deps.querier.with_market_rates(&[
+            (&"uluna".to_string(), &"uusd".to_string(), &Decimal::one()),
+            (&"uusd".to_string(), &"uluna".to_string(), &Decimal::one()),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::one(),
+        )]);
+
+        // selling half the ANC only returns 666666 uusd through the pool
+        let env = mock_env("creator", &[]);
+        let res = handle(
+            &mut deps,
+            env,
+            HandleMsg::Rebalance {
+                limits: Some(vec![SlippageLimit {
+                    info: AssetInfo::Token {
+                        contract_addr: HumanAddr::from("ANC"),
+                    },
+                    max_spread: None,
+                    min_out: Some(Uint128(700000u128)),
+                }]),
+            },
+        );
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "slippage_exceeded: Slippage exceeded for ANC: simulated return 666666 is below minimum 700000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("ANC"),
+                    msg: to_binary(&Cw20HandleMsg::Send {
+                        contract: HumanAddr::from("pairANC"),
+                        amount: Uint128(500000u128),
+                        msg: Some(
+                            to_binary(&TerraswapCw20HookMsg::Swap {
+                                max_spread: None,
+                                belief_price: None,
+                                to: None,
+                            })
+                            .unwrap()
+                        ),
+                    })
+                    .unwrap(),
+                    send: vec![],
+                }),
+                CosmosMsg::Custom(TerraMsgWrapper {
+                    route: TerraRoute::Market,
+                    msg_data: TerraMsg::Swap {
+                        trader: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        offer_coin: Coin {
+                            amount: Uint128(666666u128),
+                            denom: "uusd".to_string()
+                        },
+                        ask_denom: "uluna".to_string()
+                    }
+                }),
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    send: vec![],
+                    msg: to_binary(&HandleMsg::UpdateReserves {
+                        prev_balances: vec![
+                            Asset {
+                                info: AssetInfo::Token {
+                                    contract_addr: HumanAddr::from("ANC"),
+                                },
+                                amount: Uint128(1000000u128),
+                            },
+                            Asset {
+                                info: AssetInfo::Token {
+                                    contract_addr: HumanAddr::from("MIRROR"),
+                                },
+                                amount: Uint128(1000000u128),
+                            },
+                            Asset {
+                                info: AssetInfo::NativeToken {
+                                    denom: "uluna".to_string(),
+                                },
+                                amount: Uint128(1000000u128),
+                            },
+                        ],
+                    })
+                    .unwrap(),
+                })
+            ]
+        );
+        assert_eq!(
+            res.log,
+            vec![
+                log("nav", "4000000"),
+                log("sell", "ANC:500000"),
+                log("buy", "uluna:666666")
+            ]
+        );
+
+        // only what is left of the pool return after tax is spent
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Rebalance { limits: None }).unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("nav", "4000000"),
+                log("sell", "ANC:500000"),
+                log("buy", "uluna:653529")
+            ]
+        );
+    }
+    #[test]
+    fn poke_rebalance() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1000000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 500,
+            twap_window: 3600,
+            rebalance_tolerance: 500,
+            rebalance_interval: 86400,
+            rebalance_bounty: Uint128(1000u128),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let start = env.block.time;
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 2000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        deps.querier.with_tax(
+            Decimal::zero(),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+
+        // ANC at 2000000 uusd and luna at 1000000 uusd are well outside the band
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+        ]);
+        deps.querier.with_market_rates(&[
+            (&"uluna".to_string(), &"uusd".to_string(), &Decimal::one()),
+            (&"uusd".to_string(), &"uluna".to_string(), &Decimal::one()),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::one(),
+        )]);
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start - 3600;
+        let _res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+
+        // selling half the ANC pool returns far less than its TWAP is worth
+        let env = mock_env("keeper", &[]);
+        let res = handle(&mut deps, env, HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "slippage_exceeded: Slippage exceeded for ANC: simulated return 666666 is below minimum 950000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // anyone can poke into deep enough pools, the bounty is held back from
+        // the sale proceeds
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 2000000000u128, 1000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000u128, 1000000000u128),
+            ),
+        ]);
+        let env = mock_env("keeper", &[]);
+        let res = handle(&mut deps, env, HandleMsg::PokeRebalance {}).unwrap();
+        assert_eq!(res.messages.len(), 4);
+        assert_eq!(
+            res.messages[1],
+            CosmosMsg::Custom(TerraMsgWrapper {
+                route: TerraRoute::Market,
+                msg_data: TerraMsg::Swap {
+                    trader: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    offer_coin: Coin {
+                        amount: Uint128(998500u128),
+                        denom: "uusd".to_string()
+                    },
+                    ask_denom: "uluna".to_string()
+                }
+            })
+        );
+        assert_eq!(
+            res.messages[3],
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("keeper"),
+                amount: coins(1000u128, "uusd"),
+            })
+        );
         assert_eq!(
-            res.messages,
-            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
-                code_id: 123u64,
-                send: vec![],
-                label: None,
-                msg: to_binary(&TokenInitMsg {
-                    name: "Edge Terra USD".to_string(),
-                    symbol: "eTerra".to_string(),
-                    decimals: 6u8,
-                    initial_balances: vec![Cw20CoinHuman {
-                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        amount: Uint128(0),
-                    }],
-                    mint: Some(MinterResponse {
-                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        cap: None,
-                    }),
-                    init_hook: Some(InitHook {
-                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
-                    })
-                })
-                .unwrap(),
-            })]
+            res.log,
+            vec![
+                log("nav", "4000000"),
+                log("sell", "ANC:500000"),
+                log("buy", "uluna:998500"),
+                log("bounty", "1000")
+            ]
         );
 
-        // Register edge token contract
+        // a small drift inside the band cannot be poked before the interval
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(525000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+            ),
+        ]);
+        deps.querier.with_balance(
+            &HumanAddr::from(MOCK_CONTRACT_ADDR),
+            &coins(2000000u128, "uluna"),
+        );
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 82800;
+        let _res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 86399;
+        let res = handle(&mut deps, env.clone(), HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "within_tolerance: Basket weights are within the rebalance tolerance"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // but it can once the interval since the last rebalance has elapsed, for
+        // a bounty capped at 1% of what is sold and paid net of its tax
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+        env.block.time = start + 86400;
+        let res = handle(&mut deps, env.clone(), HandleMsg::PokeRebalance {}).unwrap();
+        assert_eq!(
+            res.messages.last(),
+            Some(&CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr::from("keeper"),
+                amount: coins(367u128, "uusd"),
+            }))
+        );
+        assert_eq!(
+            res.log,
+            vec![
+                log("nav", "4050000"),
+                log("sell", "ANC:18750"),
+                log("buy", "MIRROR:12130"),
+                log("buy", "uluna:24261"),
+                log("bounty", "371")
+            ]
+        );
+        let state: State = read_state(&deps.storage).load().unwrap();
+        assert_eq!(state.last_rebalance, start + 86400);
+
+        // a pool pushed off its TWAP ahead of the poke is refused outright
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 2200000000u128, 1000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000u128, 1000000000u128),
+            ),
+        ]);
+        env.block.time = start + 2 * 86400;
+        let res = handle(&mut deps, env.clone(), HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "price_deviation_exceeded: Price of ANC deviates more than 500 basis points from its reference: 2.2 vs 2"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // an interval too long to ever elapse leaves only the tolerance band
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 2000000000u128, 1000000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000000u128, 1000000000u128),
+            ),
+        ]);
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            rebalance_interval: Some(u64::MAX),
+            ..ConfigUpdate::default()
+        });
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::ExecuteQueuedConfig {};
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let res = handle(&mut deps, env.clone(), HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "within_tolerance: Basket weights are within the rebalance tolerance"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // and nobody can poke while swaps are not bounded at all
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            max_price_deviation: Some(0),
+            ..ConfigUpdate::default()
+        });
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::ExecuteQueuedConfig {};
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let res = handle(&mut deps, env, HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "unbounded_rebalance: Rebalancing for a bounty needs max_price_deviation to bound its swaps"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let _res = init(&mut deps, env, msg).unwrap();
         let msg = HandleMsg::RegisterETerra {};
         let env = mock_env("ETerra", &[]);
         let _res = handle(&mut deps, env, msg).unwrap();
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
+            ),
+        ]);
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(20000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
 
-        // Cannot register again
+        // only the owner can pause
+        let msg = HandleMsg::Pause {
+            operations: Some(vec![Operation::Mint]),
+        };
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, vec![log("pause", "mint")]);
+
+        let res = query(&deps, QueryMsg::PauseFlags {}).unwrap();
+        let flags: PauseFlagsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            flags,
+            PauseFlagsResponse {
+                mint: true,
+                burn: false,
+                rebalance: false,
+            }
+        );
+
+        // deposits are halted
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "paused: mint is paused"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // while holders can still exit
+        let burn_msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(5000u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, burn_msg.clone()).unwrap();
+
+        // pausing without a list halts everything
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Pause { operations: None }).unwrap();
+        assert_eq!(res.log, vec![log("pause", "mint,burn,rebalance")]);
+
+        let env = mock_env("ETerra", &[]);
+        let res = handle(&mut deps, env, burn_msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "paused: burn is paused"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let msg = HandleMsg::RedeemToken {
+            sender: HumanAddr::from("alice"),
+            prev_balance: Uint128::zero(),
+        };
+        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+        let env = mock_env("keeper", &[]);
+        let res = handle(&mut deps, env, HandleMsg::PokeRebalance {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "paused: rebalance is paused")
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // and operations resume one by one
+        let msg = HandleMsg::Unpause {
+            operations: Some(vec![Operation::Burn]),
+        };
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, vec![log("unpause", "burn")]);
+
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, burn_msg).unwrap();
+
+        let res = query(&deps, QueryMsg::PauseFlags {}).unwrap();
+        let flags: PauseFlagsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            flags,
+            PauseFlagsResponse {
+                mint: true,
+                burn: false,
+                rebalance: true,
+            }
+        );
+    }
+    #[test]
+    fn guardian() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: Some(HumanAddr::from("guardian")),
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let _res = init(&mut deps, env, msg).unwrap();
+        let msg = HandleMsg::RegisterETerra {};
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let res = query(&deps, QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.guardian, Some(HumanAddr::from("guardian")));
+
+        // the guardian can stop the fund
+        let env = mock_env("guardian", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Pause { operations: None }).unwrap();
+        assert_eq!(res.log, vec![log("pause", "mint,burn,rebalance")]);
+
+        // but not resume it, nor touch anything else
+        let env = mock_env("guardian", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Unpause { operations: None });
+        match res {
+            Err(StdError::Unauthorized { .. }) => {}
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            mint_fee: Some(100),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("guardian", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+        let msg = HandleMsg::ProposeNewOwner {
+            owner: HumanAddr::from("guardian"),
+            expires_in_blocks: 100,
+        };
+        let env = mock_env("guardian", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+        let env = mock_env("guardian", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::Rebalance { limits: None }).unwrap_err();
+
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Unpause { operations: None }).unwrap();
+        assert_eq!(res.log, vec![log("unpause", "mint,burn,rebalance")]);
+
+        // the owner can hand the role to another key at once
+        let msg = HandleMsg::UpdateGuardian {
+            guardian: Some(HumanAddr::from("newguardian")),
+        };
+        let env = mock_env("guardian", &[]);
+        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, vec![log("guardian", "newguardian")]);
+
+        let env = mock_env("guardian", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::Pause { operations: None }).unwrap_err();
+        let env = mock_env("newguardian", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::Pause { operations: None }).unwrap();
+
+        // or revoke it altogether
+        let msg = HandleMsg::UpdateGuardian { guardian: None };
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, vec![log("guardian", "")]);
+        let config = query_config(&deps).unwrap();
+        assert_eq!(config.guardian, None);
+        let env = mock_env("newguardian", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::Pause { operations: None }).unwrap_err();
+    }
+    #[test]
+    fn timelocked_config() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 86400,
+        };
+        let env = mock_env("creator", &[]);
+        let start = env.block.time;
+        let _res = init(&mut deps, env, msg).unwrap();
         let msg = HandleMsg::RegisterETerra {};
         let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let res = query(&deps, QueryMsg::PendingConfig {}).unwrap();
+        let pending: Option<PendingConfigResponse> = from_binary(&res).unwrap();
+        assert_eq!(pending, None);
+
+        let update = ConfigUpdate {
+            mint_fee: Some(100),
+            weights: Some(vec![
+                ComponentWeight {
+                    info: AssetInfo::Token {
+                        contract_addr: HumanAddr::from("ANC"),
+                    },
+                    weight: Decimal256::from_uint256(5000u128),
+                },
+                ComponentWeight {
+                    info: AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                    weight: Decimal256::from_uint256(2500u128),
+                },
+            ]),
+            ..ConfigUpdate::default()
+        };
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::UpdateConfig(update.clone())).unwrap();
+        assert_eq!(
+            res.log,
+            vec![log("execute_after", (start + 86400).to_string())]
+        );
+
+        // nothing changes while the update is queued
+        let config = query_config(&deps).unwrap();
+        assert_eq!(Decimal256::from_uint256(2500u128), config.basket[0].weight);
+        let res = query(&deps, QueryMsg::PendingConfig {}).unwrap();
+        let pending: Option<PendingConfigResponse> = from_binary(&res).unwrap();
+        assert_eq!(
+            pending,
+            Some(PendingConfigResponse {
+                update: update.clone(),
+                execute_after: start + 86400,
+            })
+        );
+
+        // an update that could not be applied is not queued
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            burn_fee: Some(10001),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
         let _res = handle(&mut deps, env, msg).unwrap_err();
 
-        let config = query_config(&deps).unwrap();
-        assert_eq!("terraswapfactory", config.terraswap_factory.as_str());
-        assert_eq!("uusd", config.stable_denom.as_str());
+        // nor one that would lock the config out of reach
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            config_timelock: Some(u64::MAX),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                format!(
+                    "invalid_timelock: Config timelock of {} seconds must not exceed 2592000",
+                    u64::MAX
+                )
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 86399;
+        let res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                format!(
+                    "timelock_not_expired: Queued config update cannot be executed before {}",
+                    start + 86400
+                )
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let mut env = mock_env("alice", &[]);
+        env.block.time = start + 86400;
+        let _res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap_err();
+
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 86400;
+        let res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("weight", "ANC:5000"),
+                log("weight", "uluna:2500"),
+                log("mint_fee", "100"),
+            ]
+        );
+        let config = query_config(&deps).unwrap();
+        assert_eq!(Decimal256::from_uint256(5000u128), config.basket[0].weight);
+        let res = query(&deps, QueryMsg::PendingConfig {}).unwrap();
+        let pending: Option<PendingConfigResponse> = from_binary(&res).unwrap();
+        assert_eq!(pending, None);
+
+        // a queued update can be cancelled by the owner
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::UpdateConfig(update)).unwrap();
+        let env = mock_env("alice", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::CancelQueuedConfig {}).unwrap_err();
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::CancelQueuedConfig {}).unwrap();
+        assert_eq!(res.log, vec![log("cancel_queued_config", "")]);
+
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::CancelQueuedConfig {});
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "no_pending_config: No config update queued")
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let mut env = mock_env("creator", &[]);
+        env.block.time = start + 2 * 86400;
+        let _res = handle(&mut deps, env, HandleMsg::ExecuteQueuedConfig {}).unwrap_err();
+    }
+    #[test]
+    fn simulate_mint_and_burn() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(3000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 100,
+            burn_fee: 100,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        deps.querier.with_market_rates(&[
+            (
+                &"uusd".to_string(),
+                &"uluna".to_string(),
+                &Decimal::percent(1),
+            ),
+            (
+                &"uluna".to_string(),
+                &"uusd".to_string(),
+                &Decimal::from_ratio(100u128, 1u128),
+            ),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(40000u128))],
+            ),
+        ]);
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(20000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        // 101000 uusd pays a 1010 fee, the rest is split over the swaps and each
+        // pays its own tax; they bring in 24151 ANC, 24151 MIRROR and 495 uluna
+        // worth 97802 against a NAV of 440000
+        let res = query(
+            &deps,
+            QueryMsg::SimulateMint {
+                amount: Uint128(101000u128),
+            },
+        )
+        .unwrap();
+        let simulation: SimulateMintResponse = from_binary(&res).unwrap();
+        assert_eq!(simulation.mint_amount, Uint128(4445u128));
+        assert_eq!(simulation.tax_amount, Uint128(1001u128));
+        assert_eq!(simulation.fee_amount, Uint128(1010u128));
+        assert_eq!(
+            simulation.swaps[0],
+            SwapRateResponse {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                offer_amount: Uint128(24749u128),
+                return_amount: Decimal256::from_uint256(24151u128),
+                spread_amount: Decimal256::from_uint256(598u128),
+                commission_amount: Decimal256::zero(),
+                tax_amount: Decimal256::from_uint256(248u128),
+            }
+        );
+        assert_eq!(
+            simulation.swaps[2],
+            SwapRateResponse {
+                info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                offer_amount: Uint128(49500u128),
+                return_amount: Decimal256::from_uint256(495u128),
+                spread_amount: Decimal256::zero(),
+                commission_amount: Decimal256::zero(),
+                tax_amount: Decimal256::from_uint256(495u128),
+            }
+        );
+
+        // a quarter of the supply sells a quarter of each component balance
+        let res = query(
+            &deps,
+            QueryMsg::SimulateBurn {
+                amount: Uint128(5000u128),
+            },
+        )
+        .unwrap();
+        let simulation: SimulateBurnResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            simulation.swaps[0],
+            SwapRateResponse {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                offer_amount: Uint128(25000u128),
+                return_amount: Decimal256::from_uint256(24390u128),
+                spread_amount: Decimal256::from_uint256(610u128),
+                commission_amount: Decimal256::zero(),
+                tax_amount: Decimal256::from_uint256(242u128),
+            }
+        );
+        // 24148 + 9801 + 75000 uusd less a 1089 fee, and the 1068 tax on sending
+        // the 107860 left
+        assert_eq!(simulation.tax_amount, Uint128(1409u128));
+        assert_eq!(simulation.fee_amount, Uint128(1089u128));
+        assert_eq!(simulation.return_amount, Uint128(106792u128));
+
+        let res = query(
+            &deps,
+            QueryMsg::SimulateBurn {
+                amount: Uint128(20001u128),
+            },
+        );
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "burn_exceeds_supply: Burn amount 20001 exceeds total supply 20000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
     }
-
     #[test]
-    fn mint() {
-        let mut deps = mock_dependencies(20, &[]);
+    fn nav() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1000u128),
+            }],
+        );
         let msg = InitMsg {
-            alloc_luna: Decimal256::from_uint256(5000u128),
-            alloc_mir: Decimal256::from_uint256(2500u128),
-            alloc_anc: Decimal256::from_uint256(2500u128),
+            basket: default_basket(),
+            guardian: None,
             stable_denom: "uusd".to_string(),
             eterra_code_id: 123u64,
             terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
         };
         let env = mock_env("creator", &[]);
         deps.querier.with_terraswap_pairs(&[
             (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
             (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
         ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 500000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100000u128))],
+            ),
+        ]);
 
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        // luna is priced by the oracle, tokens by their pool
+        let res = query(&deps, QueryMsg::Prices {}).unwrap();
+        let prices: PricesResponse = from_binary(&res).unwrap();
         assert_eq!(
-            res.messages,
-            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
-                code_id: 123u64,
-                send: vec![],
-                label: None,
-                msg: to_binary(&TokenInitMsg {
-                    name: "Edge Terra USD".to_string(),
-                    symbol: "eTerra".to_string(),
-                    decimals: 6u8,
-                    initial_balances: vec![Cw20CoinHuman {
-                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        amount: Uint128(0),
-                    }],
-                    mint: Some(MinterResponse {
-                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        cap: None,
-                    }),
-                    init_hook: Some(InitHook {
-                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
-                    })
-                })
-                .unwrap(),
-            })]
+            prices.prices,
+            vec![
+                PriceResponse {
+                    info: AssetInfo::Token {
+                        contract_addr: HumanAddr::from("ANC"),
+                    },
+                    price: Decimal256::from_uint256(2u128),
+                },
+                PriceResponse {
+                    info: AssetInfo::Token {
+                        contract_addr: HumanAddr::from("MIRROR"),
+                    },
+                    price: Decimal256::one(),
+                },
+                PriceResponse {
+                    info: AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                    price: Decimal256::from_uint256(100u128),
+                },
+            ]
         );
 
-        // Register edge token contract
-        let msg = HandleMsg::RegisterETerra {};
-        let env = mock_env("ETerra", &[]);
-        let _res = handle(&mut deps, env, msg).unwrap();
-
-        // Cannot register again
-        let msg = HandleMsg::RegisterETerra {};
-        let env = mock_env("ETerra", &[]);
-        let _res = handle(&mut deps, env, msg).unwrap_err();
+        // eTerra is priced at 1 before anything is minted
+        let res = query(&deps, QueryMsg::Nav {}).unwrap();
+        let nav: NavResponse = from_binary(&res).unwrap();
+        assert_eq!(nav.nav, Uint128(400000u128));
+        assert_eq!(nav.price, Decimal256::one());
 
-        let msg = HandleMsg::Mint {};
-        let env = mock_env(
-            "alice",
-            &[Coin {
-                denom: "uusd".to_string(),
-                amount: Uint128(100000u128),
-            }],
-        );
-        let res = handle(&mut deps, env, msg).unwrap();
-        let _balance = deps
-            .querier
-            .query_balance(MOCK_CONTRACT_ADDR, "uusd")
-            .unwrap()
-            .amount;
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(200000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
 
-        assert_eq!(Uint128(0u128), _balance);
-        assert_eq!(res.messages.len(), 4);
-        assert_eq!(res.log, vec![log("mint", "100000"),]);
+        let res = query(&deps, QueryMsg::Nav {}).unwrap();
+        let nav: NavResponse = from_binary(&res).unwrap();
         assert_eq!(
-            res.messages[0],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("pairANC"),
-                msg: to_binary(&TerraswapHandleMsg::Swap {
-                    offer_asset: Asset {
-                        info: AssetInfo::NativeToken {
-                            denom: "uusd".to_string()
+            nav,
+            NavResponse {
+                nav: Uint128(400000u128),
+                total_supply: Uint128(200000u128),
+                price: Decimal256::from_uint256(2u128),
+                components: vec![
+                    ComponentNavResponse {
+                        info: AssetInfo::Token {
+                            contract_addr: HumanAddr::from("ANC"),
                         },
-                        amount: Uint128(25000u128), // 1000 * 25%
+                        balance: Uint128(100000u128),
+                        price: Decimal256::from_uint256(2u128),
+                        value: Uint128(200000u128),
+                        weight: Decimal256::from_uint256(5000u128),
+                        target_weight: Decimal256::from_uint256(2500u128),
                     },
-                    max_spread: None,
-                    belief_price: None,
-                    to: None,
-                })
-                .unwrap(),
-                send: vec![Coin {
-                    amount: Uint128(25000u128),
-                    denom: "uusd".to_string(),
-                }],
-            })
-        );
-        assert_eq!(
-            res.messages[1],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("pairMIRROR"),
-                msg: to_binary(&TerraswapHandleMsg::Swap {
-                    offer_asset: Asset {
+                    ComponentNavResponse {
+                        info: AssetInfo::Token {
+                            contract_addr: HumanAddr::from("MIRROR"),
+                        },
+                        balance: Uint128(100000u128),
+                        price: Decimal256::one(),
+                        value: Uint128(100000u128),
+                        weight: Decimal256::from_uint256(2500u128),
+                        target_weight: Decimal256::from_uint256(2500u128),
+                    },
+                    ComponentNavResponse {
                         info: AssetInfo::NativeToken {
-                            denom: "uusd".to_string()
+                            denom: "uluna".to_string(),
                         },
-                        amount: Uint128(25000u128), // 1000 * 25$
+                        balance: Uint128(1000u128),
+                        price: Decimal256::from_uint256(100u128),
+                        value: Uint128(100000u128),
+                        weight: Decimal256::from_uint256(2500u128),
+                        target_weight: Decimal256::from_uint256(5000u128),
                     },
-                    max_spread: None,
-                    belief_price: None,
-                    to: None,
-                })
-                .unwrap(),
-                send: vec![Coin {
-                    amount: Uint128(25000u128),
-                    denom: "uusd".to_string(),
-                }],
-            })
+                ],
+            }
         );
+    }
+    #[test]
+    fn reserves() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1000u128),
+            }],
+        );
+        let msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(3000u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(2000u128))],
+            ),
+        ]);
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        let prev_balances = vec![
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                amount: Uint128(1000u128),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("MIRROR"),
+                },
+                amount: Uint128(2000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                amount: Uint128(1500u128),
+            },
+        ];
+
+        // only the contract can book its own swaps
+        let msg = HandleMsg::UpdateReserves {
+            prev_balances: prev_balances.clone(),
+        };
+        let env = mock_env("alice", &[]);
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::Unauthorized { .. }) => {}
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // gains are added, spending never takes a reserve below zero
+        let env = mock_env(MOCK_CONTRACT_ADDR, &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
         assert_eq!(
-            res.messages[2],
-            CosmosMsg::Custom(TerraMsgWrapper {
-                route: TerraRoute::Market,
-                msg_data: TerraMsg::Swap {
-                    trader: HumanAddr::from("cosmos2contract"),
-                    offer_coin: Coin {
-                        amount: Uint128(50000u128),
-                        denom: "uusd".to_string()
-                    },
-                    ask_denom: "uluna".to_string()
-                }
-            })
+            res.log,
+            vec![
+                log("reserve", "ANC:2000"),
+                log("reserve", "MIRROR:0"),
+                log("reserve", "uluna:0"),
+            ]
         );
+
+        let res = query(&deps, QueryMsg::State {}).unwrap();
+        let state: StateResponse = from_binary(&res).unwrap();
         assert_eq!(
-            res.messages[3],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("ETerra"),
-                send: vec![],
-                msg: to_binary(&Cw20HandleMsg::Mint {
-                    recipient: HumanAddr::from("alice"),
-                    amount: Uint128(100000u128),
-                })
-                .unwrap(),
-            })
+            state.reserves[0],
+            ReserveResponse {
+                info: AssetInfo::Token {
+                    contract_addr: HumanAddr::from("ANC"),
+                },
+                amount: Decimal256::from_uint256(2000u128),
+            }
         );
+
+        // anyone can report, which leaves the book untouched
+        let expected = vec![
+            log("discrepancy", "ANC:2000:3000"),
+            log("discrepancy", "MIRROR:0:2000"),
+            log("discrepancy", "uluna:0:1000"),
+        ];
+        let msg: HandleMsg = from_binary(&Binary::from(br#"{"reconcile":{}}"#)).unwrap();
+        assert_eq!(msg, HandleMsg::Reconcile { resync: None });
+        let env = mock_env("alice", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, expected);
+
+        // only the owner can resync
+        let msg = HandleMsg::Reconcile { resync: Some(true) };
+        let env = mock_env("alice", &[]);
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::Unauthorized { .. }) => {}
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.log, expected);
+
+        // nothing left to report once the book is resynced
+        let env = mock_env("creator", &[]);
+        let res = handle(&mut deps, env, HandleMsg::Reconcile { resync: None }).unwrap();
+        assert_eq!(res.log, vec![]);
     }
     #[test]
-    fn burn() {
+    fn price_deviation_bound() {
         let mut deps = mock_dependencies(
             20,
             &[Coin {
                 denom: "uluna".to_string(),
-                amount: Uint128(1000000u128),
+                amount: Uint128(1000u128),
+            }],
+        );
+        let mut msg = InitMsg {
+            basket: default_basket(),
+            guardian: None,
+            stable_denom: "uusd".to_string(),
+            eterra_code_id: 123u64,
+            terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 10001,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
+        };
+        let env = mock_env("creator", &[]);
+        let res = init(&mut deps, env.clone(), msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "invalid_price_deviation: Price deviation of 10001 basis points must not exceed 10000"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // tokens are only bounded against their TWAP, so one must be kept
+        msg.max_price_deviation = 500;
+        let res = init(&mut deps, env.clone(), msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "no_reference_price: ANC has no reference price to bound its pool price by while twap_window is 0"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        msg.twap_window = 3600;
+        deps.querier.with_terraswap_pairs(&[
+            (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
+            (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
+        ]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
+            ),
+        ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        deps.querier.with_market_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(96u128, 1u128),
+        )]);
+        let _res = init(&mut deps, env.clone(), msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        // token references need a window of price history first
+        let mut env = mock_env("keeper", &[]);
+        env.block.time -= 3600;
+        let _res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+
+        // the market trading luna 4% under the oracle is within the bound
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let _res = handle(&mut deps, env.clone(), HandleMsg::Mint { limits: None }).unwrap();
+
+        // 6% under is not, both minting and burning are refused
+        deps.querier.with_market_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(94u128, 1u128),
+        )]);
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "price_deviation_exceeded: Price of uluna deviates more than 500 basis points from its reference: 94 vs 100"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(1000u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+        let env = mock_env("ETerra", &[]);
+        let res = handle(&mut deps, env, msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "price_deviation_exceeded: Price of uluna deviates more than 500 basis points from its reference: 94 vs 100"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // a CW20 pool pushed 10% over its TWAP is refused as well
+        deps.querier.with_market_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1100000u128, 1000000u128),
+            ),
+        ]);
+        let mut env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
             }],
         );
+        env.block.time += 3600;
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "price_deviation_exceeded: Price of MIRROR deviates more than 500 basis points from its reference: 1.1 vs 1"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // nor can the TWAP be switched off while the bound is on
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            twap_window: Some(0),
+            ..ConfigUpdate::default()
+        });
+        let env = mock_env("creator", &[]);
+        let _res = handle(&mut deps, env, msg).unwrap_err();
+    }
+    #[test]
+    fn twap() {
+        let mut deps = mock_dependencies(20, &[]);
         let msg = InitMsg {
-            alloc_luna: Decimal256::from_uint256(5000u128),
-            alloc_mir: Decimal256::from_uint256(2500u128),
-            alloc_anc: Decimal256::from_uint256(2500u128),
+            basket: default_basket(),
+            guardian: None,
             stable_denom: "uusd".to_string(),
             eterra_code_id: 123u64,
             terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 5000,
+            twap_window: 3600,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 0,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
         };
         let env = mock_env("creator", &[]);
+        let start = env.block.time;
         deps.querier.with_terraswap_pairs(&[
             (&"uusdANC".to_string(), &HumanAddr::from("pairANC")),
             (&"uusdMIRROR".to_string(), &HumanAddr::from("pairMIRROR")),
         ]);
-        deps.querier.with_token_balances(&[
+        deps.querier.with_terraswap_pools(&[
             (
-                &HumanAddr::from("ETerra"),
-                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(5000u128))], // User balance sended
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1000000u128, 1000000u128),
             ),
             (
-                &HumanAddr::from("terra1747mad58h0w4y589y3sk84r5efqdev9q4r02pc"),
-                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000u128))],
             ),
             (
-                &HumanAddr::from("uusdMIRROR"),
-                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000000u128))],
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
             ),
         ]);
+        deps.querier.with_exchange_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        deps.querier.with_market_rates(&[(
+            &"uluna".to_string(),
+            &"uusd".to_string(),
+            &Decimal::from_ratio(100u128, 1u128),
+        )]);
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
+        let anc = AssetInfo::Token {
+            contract_addr: HumanAddr::from("ANC"),
+        };
+        let msg = QueryMsg::Twap {
+            asset: anc.clone(),
+            window_seconds: 3600,
+        };
+        let res = query(&deps, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(
+                    msg,
+                    "no_price_history: Price history of ANC does not cover the TWAP window"
+                )
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // anyone can record prices
+        let env = mock_env("keeper", &[]);
+        let res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
         assert_eq!(
-            res.messages,
-            vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
-                code_id: 123u64,
-                send: vec![],
-                label: None,
-                msg: to_binary(&TokenInitMsg {
-                    name: "Edge Terra USD".to_string(),
-                    symbol: "eTerra".to_string(),
-                    decimals: 6u8,
-                    initial_balances: vec![Cw20CoinHuman {
-                        address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        amount: Uint128(0),
-                    }],
-                    mint: Some(MinterResponse {
-                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        cap: None,
-                    }),
-                    init_hook: Some(InitHook {
-                        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        msg: to_binary(&HandleMsg::RegisterETerra {}).unwrap(),
-                    })
-                })
-                .unwrap(),
-            })]
+            res.log,
+            vec![
+                log("price", "ANC:1"),
+                log("price", "MIRROR:1"),
+                log("price", "uluna:100"),
+            ]
         );
 
-        // Register edge token contract
-        let msg = HandleMsg::RegisterETerra {};
-        let env = mock_env("ETerra", &[]);
-        let _res = handle(&mut deps, env, msg).unwrap();
-
-        // Cannot register again
-        let msg = HandleMsg::RegisterETerra {};
-        let env = mock_env("ETerra", &[]);
-        let _res = handle(&mut deps, env, msg).unwrap_err();
+        // but not more often than a sixteenth of the window
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 224;
+        let res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+        assert_eq!(res.log, vec![]);
 
-        let mut state: State = read_state(&deps.storage).load().unwrap();
-        state.total_supply = Uint128::from(20000u128);
-        store_state(&mut deps.storage).save(&state).unwrap();
+        // a history shorter than the window prices nothing, so a fresh fund can
+        // neither be valued nor minted into at a single spot price
+        let res = query(&deps, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "no_price_history: Price history of ANC does not cover the TWAP window"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let _res = query(&deps, QueryMsg::Nav {}).unwrap_err();
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
+        );
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "no_price_history: Price history of ANC does not cover the TWAP window"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
 
-        let msg = HandleMsg::Burn {};
-        let env = mock_env("alice", &[]);
-        let res = handle(&mut deps, env, msg).unwrap();
+        // ANC trades at 1 for the first half hour and at 1.5 for the second
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1500000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 1800;
+        let _res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 3600;
+        let _res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
 
-        // assert_eq!(res.log, vec![]);
-        assert_eq!(
-            res.messages[0],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("ETerra"),
-                msg: to_binary(&Cw20HandleMsg::Send {
-                    contract: HumanAddr::from("pairANC"),
-                    amount: Uint128(62500u128),
-                    msg: Some(
-                        to_binary(&TerraswapCw20HookMsg::Swap {
-                            max_spread: None,
-                            belief_price: None,
-                            to: None,
-                        })
-                        .unwrap()
-                    ),
-                })
-                .unwrap(),
-                send: vec![],
-            })
-        );
+        let res = query(&deps, msg).unwrap();
+        let twap: TwapResponse = from_binary(&res).unwrap();
         assert_eq!(
-            res.messages[1],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("ETerra"),
-                msg: to_binary(&Cw20HandleMsg::Send {
-                    contract: HumanAddr::from("pairMIRROR"),
-                    amount: Uint128(62500u128),
-                    msg: Some(
-                        to_binary(&TerraswapCw20HookMsg::Swap {
-                            max_spread: None,
-                            belief_price: None,
-                            to: None,
-                        })
-                        .unwrap()
-                    ),
-                })
-                .unwrap(),
-                send: vec![],
-            })
+            twap,
+            TwapResponse {
+                info: anc.clone(),
+                price: Decimal256::percent(125),
+                start_time: start,
+                end_time: start + 3600,
+            }
         );
-        assert_eq!(
-            res.messages[2],
-            CosmosMsg::Custom(TerraMsgWrapper {
-                route: TerraRoute::Market,
-                msg_data: TerraMsg::Swap {
-                    trader: HumanAddr::from("cosmos2contract"),
-                    offer_coin: Coin {
-                        amount: Uint128(125000u128),
-                        denom: "uluna".to_string()
-                    },
-                    ask_denom: "uusd".to_string()
-                }
-            })
+
+        let msg = QueryMsg::Twap {
+            asset: anc,
+            window_seconds: 1800,
+        };
+        let res = query(&deps, msg).unwrap();
+        let twap: TwapResponse = from_binary(&res).unwrap();
+        assert_eq!(twap.price, Decimal256::percent(150));
+        assert_eq!(twap.start_time, start + 1800);
+
+        // the NAV goes by the TWAP rather than the spot price
+        let res = query(&deps, QueryMsg::Nav {}).unwrap();
+        let nav: NavResponse = from_binary(&res).unwrap();
+        assert_eq!(nav.components[0].price, Decimal256::percent(125));
+        assert_eq!(nav.nav, Uint128(1250u128));
+
+        // and a pool trading more than 50% off its TWAP trips the circuit breaker
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 3000000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        let mut env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
         );
-        assert_eq!(
-            res.messages[3],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from("ETerra"),
-                send: vec![],
-                msg: to_binary(&Cw20HandleMsg::Burn {
-                    amount: Uint128(5000u128),
-                })
-                .unwrap(),
-            })
+        env.block.time = start + 3600;
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "price_deviation_exceeded: Price of ANC deviates more than 5000 basis points from its reference: 3 vs 1.25"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // recording the pushed pool only moves ANC to within 50% of its TWAP of
+        // 1.28125 over the window up to then
+        let mut env = mock_env("keeper", &[]);
+        env.block.time = start + 3825;
+        let res = handle(&mut deps, env, HandleMsg::RecordPrices {}).unwrap();
+        assert_eq!(res.log[0], log("price", "ANC:1.921875"));
+
+        // the window is capped at a day
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            twap_window: Some(86401),
+            ..ConfigUpdate::default()
+        });
+        let res = handle(&mut deps, mock_env("creator", &[]), msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "invalid_twap_window: TWAP window of 86401 seconds must not exceed 86400"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // raising it leaves mints waiting for the history to refill, but holders
+        // can still burn
+        let msg = HandleMsg::UpdateConfig(ConfigUpdate {
+            twap_window: Some(7200),
+            ..ConfigUpdate::default()
+        });
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        let msg = HandleMsg::ExecuteQueuedConfig {};
+        let _res = handle(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        deps.querier.with_terraswap_pools(&[
+            (
+                &HumanAddr::from("pairANC"),
+                &mock_pool("uusd", "ANC", 1500000u128, 1000000u128),
+            ),
+            (
+                &HumanAddr::from("pairMIRROR"),
+                &mock_pool("uusd", "MIRROR", 1000000u128, 1000000u128),
+            ),
+        ]);
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(1000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        let mut env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(100000u128),
+            }],
         );
-        assert_eq!(
-            res.messages[4],
-            CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                send: vec![],
-                msg: to_binary(&HandleMsg::RedeemToken {
-                    sender: HumanAddr::from("alice")
-                })
-                .unwrap()
-            })
-        )
+        env.block.time = start + 4050;
+        let res = handle(&mut deps, env, HandleMsg::Mint { limits: None });
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "no_price_history: Price history of ANC does not cover the TWAP window"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: HumanAddr::from("alice"),
+            amount: Uint128(100u128),
+            msg: Some(to_binary(&Cw20HookMsg::Burn { limits: None }).unwrap()),
+        });
+        let mut env = mock_env("ETerra", &[]);
+        env.block.time = start + 4050;
+        let _res = handle(&mut deps, env, msg).unwrap();
     }
     #[test]
-    fn redeem_token() {
+    fn mint_in_kind() {
+        // 1000 uluna held plus 300 sent with the deposit
         let mut deps = mock_dependencies(
             20,
-            &[
-                Coin {
-                    denom: "uluna".to_string(),
-                    amount: Uint128(1000000u128),
-                },
-                Coin {
-                    denom: "uusd".to_string(),
-                    amount: Uint128(10000u128),
-                },
-            ],
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(1300u128),
+            }],
         );
         let msg = InitMsg {
-            alloc_luna: Decimal256::from_uint256(5000u128),
-            alloc_mir: Decimal256::from_uint256(2500u128),
-            alloc_anc: Decimal256::from_uint256(2500u128),
+            basket: default_basket(),
+            guardian: None,
             stable_denom: "uusd".to_string(),
             eterra_code_id: 123u64,
             terraswap_factory: HumanAddr("terraswapfactory".to_string()),
+            max_spread: None,
+            max_price_deviation: 0,
+            twap_window: 0,
+            rebalance_tolerance: 0,
+            rebalance_interval: 0,
+            rebalance_bounty: Uint128::zero(),
+            mint_fee: 100,
+            burn_fee: 0,
+            management_fee: 0,
+            fee_collector: HumanAddr::from("collector"),
+            config_timelock: 0,
         };
         let env = mock_env("creator", &[]);
-
-        // we can just call .unwrap() to assert this was a success
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("ANC"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(3001u128))],
+            ),
+            (
+                &HumanAddr::from("MIRROR"),
+                &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(2000u128))],
+            ),
+        ]);
         let _res = init(&mut deps, env, msg).unwrap();
-        // Register edge token contract
-        let msg = HandleMsg::RegisterETerra {};
-        let env = mock_env("ETerra", &[]);
-        let _res = handle(&mut deps, env, msg).unwrap();
 
-        let msg = HandleMsg::RedeemToken {
-            sender: HumanAddr::from("alice"),
+        // nothing to mint against before the eTerra token is registered
+        let msg = HandleMsg::MintInKind {
+            amount: Uint128(100u128),
         };
         let env = mock_env("alice", &[]);
-        // invalid sender
-        let _res = handle(&mut deps, env, msg.clone()).unwrap_err();
-        let env = mock_env(HumanAddr::from(MOCK_CONTRACT_ADDR), &[]);
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.starts_with("not_registered: ")),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let env = mock_env("ETerra", &[]);
+        let _res = handle(&mut deps, env, HandleMsg::RegisterETerra {}).unwrap();
+
+        // an empty fund has no proportions to deposit in
+        let env = mock_env("alice", &[]);
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "empty_fund: No basket is held yet to mint in kind against"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let mut state: State = read_state(&deps.storage).load().unwrap();
+        state.total_supply = Uint128::from(1000u128);
+        store_state(&mut deps.storage).save(&state).unwrap();
+
+        // 100 eTerra out of 1000 takes a tenth of each holding, rounded up
+        let env = mock_env(
+            "alice",
+            &[Coin {
+                denom: "uluna".to_string(),
+                amount: Uint128(50u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg.clone());
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "insufficient_deposit: Deposit of 50 uluna is below the 125 required"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // uusd sent by mistake is refunded alongside the uluna excess, both net of
+        // the tax on sending them back
+        deps.querier.with_tax(
+            Decimal::percent(1),
+            &[(&"uusd".to_string(), &Uint128(1000000u128))],
+        );
+        let env = mock_env(
+            "alice",
+            &[
+                Coin {
+                    denom: "uluna".to_string(),
+                    amount: Uint128(300u128),
+                },
+                Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(1010u128),
+                },
+            ],
+        );
         let res = handle(&mut deps, env, msg).unwrap();
         assert_eq!(
-            res.messages[0],
-            CosmosMsg::Bank(BankMsg::Send {
-                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                to_address: HumanAddr::from("alice"),
-                amount: coins(10000u128, &"uusd".to_string()),
-            })
-        )
+            res.messages,
+            vec![
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("ANC"),
+                    send: vec![],
+                    msg: to_binary(&Cw20HandleMsg::TransferFrom {
+                        owner: HumanAddr::from("alice"),
+                        recipient: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        amount: Uint128(301u128),
+                    })
+                    .unwrap(),
+                }),
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("MIRROR"),
+                    send: vec![],
+                    msg: to_binary(&Cw20HandleMsg::TransferFrom {
+                        owner: HumanAddr::from("alice"),
+                        recipient: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        amount: Uint128(200u128),
+                    })
+                    .unwrap(),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("alice"),
+                    amount: vec![
+                        Coin {
+                            denom: "uluna".to_string(),
+                            amount: Uint128(200u128),
+                        },
+                        Coin {
+                            denom: "uusd".to_string(),
+                            amount: Uint128(1000u128),
+                        },
+                    ],
+                }),
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("ETerra"),
+                    send: vec![],
+                    msg: to_binary(&Cw20HandleMsg::Mint {
+                        recipient: HumanAddr::from("alice"),
+                        amount: Uint128(99u128),
+                    })
+                    .unwrap(),
+                }),
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("ETerra"),
+                    send: vec![],
+                    msg: to_binary(&Cw20HandleMsg::Mint {
+                        recipient: HumanAddr::from("collector"),
+                        amount: Uint128(1u128),
+                    })
+                    .unwrap(),
+                }),
+            ]
+        );
+        assert_eq!(
+            res.log,
+            vec![
+                log("deposit", "ANC:301"),
+                log("deposit", "MIRROR:200"),
+                log("deposit", "uluna:100"),
+                log("mint", "100"),
+                log("fee", "1"),
+                log("management_fee", "0"),
+            ]
+        );
+
+        let state: State = read_state(&deps.storage).load().unwrap();
+        assert_eq!(state.total_supply, Uint128(1100u128));
+        assert_eq!(state.reserves[0].amount, Decimal256::from_uint256(301u128));
     }
 }
@@ -0,0 +1,251 @@
+use cosmwasm_bignumber::Decimal256;
+use cosmwasm_std::{StdError, Uint128};
+use snafu::Snafu;
+
+/// Errors raised by the index contract. They are converted into `StdError` at the
+/// entry points, where `code` gives clients a stable prefix to match on.
+#[derive(Debug, Snafu)]
+pub enum ContractError {
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+
+    #[snafu(display("Unauthorized"))]
+    Unauthorized {},
+
+    #[snafu(display("eTerra token is not registered yet"))]
+    NotRegistered {},
+
+    #[snafu(display("Deposit amount must be greater than 0 {}", denom))]
+    ZeroDeposit { denom: String },
+
+    #[snafu(display("No Terraswap pair found for {}", asset))]
+    PairNotFound { asset: String },
+
+    #[snafu(display(
+        "Slippage exceeded for {}: simulated return {} is below minimum {}",
+        asset,
+        return_amount,
+        min_out
+    ))]
+    SlippageExceeded {
+        asset: String,
+        return_amount: Uint128,
+        min_out: Uint128,
+    },
+
+    #[snafu(display("Weights must sum to {} basis points, got {}", expected, total))]
+    AllocationSumInvalid { expected: u128, total: Decimal256 },
+
+    #[snafu(display("Weight of {} must be greater than 0", asset))]
+    ZeroWeight { asset: String },
+
+    #[snafu(display("Basket must hold at least one component"))]
+    EmptyBasket {},
+
+    #[snafu(display("Duplicate basket component: {}", asset))]
+    DuplicateComponent { asset: String },
+
+    #[snafu(display("{} is not a basket component", asset))]
+    NotBasketComponent { asset: String },
+
+    #[snafu(display("No oracle price for {}", asset))]
+    PriceNotFound { asset: String },
+
+    #[snafu(display("Price history of {} does not cover the TWAP window", asset))]
+    NoPriceHistory { asset: String },
+
+    #[snafu(display(
+        "{} has no reference price to bound its pool price by while twap_window is 0",
+        asset
+    ))]
+    NoReferencePrice { asset: String },
+
+    #[snafu(display("Market route is only available for native tokens: {}", asset))]
+    MarketRouteNotNative { asset: String },
+
+    #[snafu(display(
+        "Invalid stable denom \"{}\": expected a lowercase micro denom such as \"uusd\"",
+        denom
+    ))]
+    InvalidStableDenom { denom: String },
+
+    #[snafu(display("max_spread must not exceed 1"))]
+    InvalidMaxSpread {},
+
+    #[snafu(display("Fee of {} basis points must not exceed {}", fee, max))]
+    InvalidFee { fee: u64, max: u128 },
+
+    #[snafu(display(
+        "Rebalance tolerance of {} basis points must not exceed {}",
+        tolerance,
+        max
+    ))]
+    InvalidTolerance { tolerance: u64, max: u128 },
+
+    #[snafu(display(
+        "Price deviation of {} basis points must not exceed {}",
+        deviation,
+        max
+    ))]
+    InvalidPriceDeviation { deviation: u64, max: u128 },
+
+    #[snafu(display(
+        "Price of {} deviates more than {} basis points from its reference: {} vs {}",
+        asset,
+        max_deviation,
+        price,
+        reference
+    ))]
+    PriceDeviationExceeded {
+        asset: String,
+        price: Decimal256,
+        reference: Decimal256,
+        max_deviation: u64,
+    },
+
+    #[snafu(display("Basket weights are within the rebalance tolerance"))]
+    WithinTolerance {},
+
+    #[snafu(display("Rebalancing for a bounty needs max_price_deviation to bound its swaps"))]
+    UnboundedRebalance {},
+
+    #[snafu(display("Burn amount {} exceeds total supply {}", amount, total_supply))]
+    BurnExceedsSupply {
+        amount: Uint128,
+        total_supply: Uint128,
+    },
+
+    #[snafu(display("Net asset value is zero while eTerra supply is outstanding"))]
+    ZeroNav {},
+
+    #[snafu(display("No basket is held yet to mint in kind against"))]
+    EmptyFund {},
+
+    #[snafu(display("Deposit of {} {} is below the {} required", amount, asset, required))]
+    InsufficientDeposit {
+        asset: String,
+        amount: Uint128,
+        required: Uint128,
+    },
+
+    #[snafu(display("Invalid request: \"burn\" message not included in request"))]
+    MissingHookMsg {},
+
+    #[snafu(display("{} is paused", operation))]
+    Paused { operation: String },
+
+    #[snafu(display("Config timelock of {} seconds must not exceed {}", timelock, max))]
+    InvalidTimelock { timelock: u64, max: u64 },
+
+    #[snafu(display("TWAP window of {} seconds must not exceed {}", twap_window, max))]
+    InvalidTwapWindow { twap_window: u64, max: u64 },
+
+    #[snafu(display("No config update queued"))]
+    NoPendingConfig {},
+
+    #[snafu(display("Queued config update cannot be executed before {}", execute_after))]
+    TimelockNotExpired { execute_after: u64 },
+
+    #[snafu(display("No ownership proposal in progress"))]
+    NoPendingOwner {},
+
+    #[snafu(display("Ownership proposal expired"))]
+    OwnershipProposalExpired {},
+
+    #[snafu(display("Expiry of {} blocks overflows the block height", expires_in_blocks))]
+    InvalidExpiry { expires_in_blocks: u64 },
+}
+
+impl ContractError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::Std { .. } => "std",
+            ContractError::Unauthorized {} => "unauthorized",
+            ContractError::NotRegistered {} => "not_registered",
+            ContractError::ZeroDeposit { .. } => "zero_deposit",
+            ContractError::PairNotFound { .. } => "pair_not_found",
+            ContractError::SlippageExceeded { .. } => "slippage_exceeded",
+            ContractError::AllocationSumInvalid { .. } => "allocation_sum_invalid",
+            ContractError::ZeroWeight { .. } => "zero_weight",
+            ContractError::EmptyBasket {} => "empty_basket",
+            ContractError::DuplicateComponent { .. } => "duplicate_component",
+            ContractError::NotBasketComponent { .. } => "not_basket_component",
+            ContractError::PriceNotFound { .. } => "price_not_found",
+            ContractError::NoPriceHistory { .. } => "no_price_history",
+            ContractError::NoReferencePrice { .. } => "no_reference_price",
+            ContractError::MarketRouteNotNative { .. } => "market_route_not_native",
+            ContractError::InvalidStableDenom { .. } => "invalid_stable_denom",
+            ContractError::InvalidMaxSpread {} => "invalid_max_spread",
+            ContractError::InvalidFee { .. } => "invalid_fee",
+            ContractError::InvalidTolerance { .. } => "invalid_tolerance",
+            ContractError::InvalidPriceDeviation { .. } => "invalid_price_deviation",
+            ContractError::PriceDeviationExceeded { .. } => "price_deviation_exceeded",
+            ContractError::WithinTolerance {} => "within_tolerance",
+            ContractError::UnboundedRebalance {} => "unbounded_rebalance",
+            ContractError::BurnExceedsSupply { .. } => "burn_exceeds_supply",
+            ContractError::ZeroNav {} => "zero_nav",
+            ContractError::EmptyFund {} => "empty_fund",
+            ContractError::InsufficientDeposit { .. } => "insufficient_deposit",
+            ContractError::MissingHookMsg {} => "missing_hook_msg",
+            ContractError::Paused { .. } => "paused",
+            ContractError::InvalidTimelock { .. } => "invalid_timelock",
+            ContractError::InvalidTwapWindow { .. } => "invalid_twap_window",
+            ContractError::NoPendingConfig {} => "no_pending_config",
+            ContractError::TimelockNotExpired { .. } => "timelock_not_expired",
+            ContractError::NoPendingOwner {} => "no_pending_owner",
+            ContractError::OwnershipProposalExpired {} => "ownership_proposal_expired",
+            ContractError::InvalidExpiry { .. } => "invalid_expiry",
+        }
+    }
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std { source } => source,
+            ContractError::Unauthorized {} => StdError::unauthorized(),
+            err => StdError::generic_err(format!("{}: {}", err.code(), err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_std_error() {
+        let err: StdError = ContractError::ZeroDeposit {
+            denom: "uusd".to_string(),
+        }
+        .into();
+        match err {
+            StdError::GenericErr { msg, .. } => {
+                assert_eq!(
+                    msg,
+                    "zero_deposit: Deposit amount must be greater than 0 uusd"
+                )
+            }
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        let err: StdError = ContractError::Unauthorized {}.into();
+        match err {
+            StdError::Unauthorized { .. } => {}
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+
+        // standard errors pass through untouched
+        let err: StdError = ContractError::from(StdError::not_found("config")).into();
+        match err {
+            StdError::NotFound { kind, .. } => assert_eq!(kind, "config"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+}
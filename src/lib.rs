@@ -1,7 +1,10 @@
 pub mod contract;
+pub mod error;
 #[cfg(test)]
 mod mock_querier;
 pub mod msg;
+pub mod pricing;
+pub mod querier;
 pub mod state;
 
 mod math;
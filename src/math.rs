@@ -1,3 +1,4 @@
+use cosmwasm_bignumber::{Decimal256, Uint256};
 use cosmwasm_std::{Decimal, Uint128};
 
 const DECIMAL_FRACTIONAL: Uint128 = Uint128(1_000_000_000u128);
@@ -8,6 +9,21 @@ pub fn decimal_division(a: Uint128, b: Decimal) -> Uint128 {
     decimal * DECIMAL_FRACTIONAL
 }
 
+/// return a / b as a Decimal256, b must not be zero
+pub fn decimal_ratio(a: Uint128, b: Uint128) -> Decimal256 {
+    Decimal256::from_uint256(Uint256::from(a)) / Decimal256::from_uint256(Uint256::from(b))
+}
+
+/// return a * nom / denom, rounded up
+pub fn ceil_multiply_ratio(a: Uint128, nom: Uint128, denom: Uint128) -> Uint128 {
+    let floor = a.multiply_ratio(nom, denom);
+    if Uint256::from(floor) * Uint256::from(denom) == Uint256::from(a) * Uint256::from(nom) {
+        floor
+    } else {
+        floor + Uint128(1)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -19,4 +35,16 @@ mod tests {
         let res = decimal_division(a, b);
         assert_eq!(res, Uint128(500));
     }
+
+    #[test]
+    fn test_ceil_multiply_ratio() {
+        assert_eq!(
+            ceil_multiply_ratio(Uint128(100), Uint128(1), Uint128(4)),
+            Uint128(25)
+        );
+        assert_eq!(
+            ceil_multiply_ratio(Uint128(100), Uint128(1), Uint128(3)),
+            Uint128(34)
+        );
+    }
 }
@@ -10,8 +10,12 @@ use cosmwasm_storage::to_length_prefixed;
 
 use std::collections::HashMap;
 
-use terra_cosmwasm::{TaxCapResponse, TaxRateResponse, TerraQuery, TerraQueryWrapper, TerraRoute};
-use terraswap::asset::{AssetInfo, PairInfo};
+use terra_cosmwasm::{
+    ExchangeRateItem, ExchangeRatesResponse, SwapResponse, TaxCapResponse, TaxRateResponse,
+    TerraQuery, TerraQueryWrapper, TerraRoute,
+};
+use terraswap::asset::{Asset, AssetInfo, PairInfo};
+use terraswap::pair::{PoolResponse, SimulationResponse};
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
 /// this uses our CustomQuerier.
@@ -38,6 +42,9 @@ pub struct WasmMockQuerier {
     token_querier: TokenQuerier,
     tax_querier: TaxQuerier,
     terraswap_factory_querier: TerraswapFactoryQuerier,
+    terraswap_pool_querier: TerraswapPoolQuerier,
+    market_querier: MarketQuerier,
+    oracle_querier: OracleQuerier,
     canonical_length: usize,
 }
 
@@ -115,6 +122,53 @@ pub(crate) fn pairs_to_map(pairs: &[(&String, &HumanAddr)]) -> HashMap<String, H
     pairs_map
 }
 
+#[derive(Clone, Default)]
+pub struct TerraswapPoolQuerier {
+    pools: HashMap<HumanAddr, [Asset; 2]>,
+}
+
+impl TerraswapPoolQuerier {
+    pub fn new(pools: &[(&HumanAddr, &[Asset; 2])]) -> Self {
+        let mut pools_map: HashMap<HumanAddr, [Asset; 2]> = HashMap::new();
+        for (pair, assets) in pools.iter() {
+            pools_map.insert(HumanAddr::from(pair), (*assets).clone());
+        }
+        TerraswapPoolQuerier { pools: pools_map }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MarketQuerier {
+    // (offer denom, ask denom) => ask amount received per offer unit
+    rates: HashMap<(String, String), Decimal>,
+}
+
+impl MarketQuerier {
+    pub fn new(rates: &[(&String, &String, &Decimal)]) -> Self {
+        let mut rates_map: HashMap<(String, String), Decimal> = HashMap::new();
+        for (offer, ask, rate) in rates.iter() {
+            rates_map.insert((offer.to_string(), ask.to_string()), **rate);
+        }
+        MarketQuerier { rates: rates_map }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct OracleQuerier {
+    // (base denom, quote denom) => quote amount per base unit
+    rates: HashMap<(String, String), Decimal>,
+}
+
+impl OracleQuerier {
+    pub fn new(rates: &[(&String, &String, &Decimal)]) -> Self {
+        let mut rates_map: HashMap<(String, String), Decimal> = HashMap::new();
+        for (base, quote, rate) in rates.iter() {
+            rates_map.insert((base.to_string(), quote.to_string()), **rate);
+        }
+        OracleQuerier { rates: rates_map }
+    }
+}
+
 impl Querier for WasmMockQuerier {
     fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
         // MockQuerier doesn't support Custom, so we ignore it completely here
@@ -135,6 +189,8 @@ impl Querier for WasmMockQuerier {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Pair { asset_infos: [AssetInfo; 2] },
+    Pool {},
+    Simulation { offer_asset: Asset },
 }
 
 impl WasmMockQuerier {
@@ -161,36 +217,67 @@ impl WasmMockQuerier {
                         }
                         _ => panic!("DO NOT ENTER HERE"),
                     }
+                } else if route == &TerraRoute::Market {
+                    match query_data {
+                        TerraQuery::Swap {
+                            offer_coin,
+                            ask_denom,
+                        } => {
+                            let rate = self
+                                .market_querier
+                                .rates
+                                .get(&(offer_coin.denom.clone(), ask_denom.clone()))
+                                .copied()
+                                .unwrap_or_default();
+                            let res = SwapResponse {
+                                receive: Coin {
+                                    denom: ask_denom.clone(),
+                                    amount: offer_coin.amount * rate,
+                                },
+                            };
+                            Ok(to_binary(&res))
+                        }
+                        _ => panic!("DO NOT ENTER HERE"),
+                    }
+                } else if route == &TerraRoute::Oracle {
+                    match query_data {
+                        TerraQuery::ExchangeRates {
+                            base_denom,
+                            quote_denoms,
+                        } => {
+                            let res = ExchangeRatesResponse {
+                                base_denom: base_denom.to_string(),
+                                exchange_rates: quote_denoms
+                                    .iter()
+                                    .map(|quote_denom| ExchangeRateItem {
+                                        quote_denom: quote_denom.to_string(),
+                                        exchange_rate: self
+                                            .oracle_querier
+                                            .rates
+                                            .get(&(base_denom.to_string(), quote_denom.to_string()))
+                                            .copied()
+                                            .unwrap_or_default(),
+                                    })
+                                    .collect(),
+                            };
+                            Ok(to_binary(&res))
+                        }
+                        _ => panic!("DO NOT ENTER HERE"),
+                    }
                 } else {
                     panic!("DO NOT ENTER HERE")
                 }
             }
-            QueryRequest::Wasm(WasmQuery::Smart {
-                contract_addr: _,
-                msg,
-            }) => match from_binary(&msg).unwrap() {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => match from_binary(msg)
+                .unwrap()
+            {
                 QueryMsg::Pair { asset_infos } => {
-                    let key = asset_infos[0].to_string()
-                        + if asset_infos[1].to_string().as_str()
-                            == "terra1747mad58h0w4y589y3sk84r5efqdev9q4r02pc"
-                        {
-                            "ANC"
-                        } else {
-                            "MIRROR"
-                        };
-                    println!("{}", key);
+                    let key = asset_infos[0].to_string() + asset_infos[1].to_string().as_str();
                     match self.terraswap_factory_querier.pairs.get(&key) {
                         Some(v) => Ok(to_binary(&PairInfo {
                             contract_addr: v.clone(),
                             liquidity_token: HumanAddr::from("liquidity"),
-                            asset_infos: [
-                                AssetInfo::NativeToken {
-                                    denom: "uusd".to_string(),
-                                },
-                                AssetInfo::NativeToken {
-                                    denom: "uusd".to_string(),
-                                },
-                            ],
+                            asset_infos: asset_infos.clone(),
                         })),
                         None => Err(SystemError::InvalidRequest {
                             error: "No pair info exists".to_string(),
@@ -198,6 +285,46 @@ impl WasmMockQuerier {
                         }),
                     }
                 }
+                QueryMsg::Pool {} => match self.terraswap_pool_querier.pools.get(contract_addr) {
+                    Some(assets) => Ok(to_binary(&PoolResponse {
+                        assets: assets.clone(),
+                        total_share: Uint128::zero(),
+                    })),
+                    None => Err(SystemError::InvalidRequest {
+                        error: "No pool info exists".to_string(),
+                        request: msg.as_slice().into(),
+                    }),
+                },
+                QueryMsg::Simulation { offer_asset } => {
+                    match self.terraswap_pool_querier.pools.get(contract_addr) {
+                        Some(assets) => {
+                            // constant product without commission
+                            let (offer_pool, ask_pool) = if assets[0].info.equal(&offer_asset.info)
+                            {
+                                (assets[0].amount, assets[1].amount)
+                            } else {
+                                (assets[1].amount, assets[0].amount)
+                            };
+                            let return_amount = ask_pool.multiply_ratio(
+                                offer_asset.amount,
+                                offer_pool + offer_asset.amount,
+                            );
+                            let spread_amount =
+                                (offer_asset.amount.multiply_ratio(ask_pool, offer_pool)
+                                    - return_amount)
+                                    .unwrap_or_default();
+                            Ok(to_binary(&SimulationResponse {
+                                return_amount,
+                                spread_amount,
+                                commission_amount: Uint128::zero(),
+                            }))
+                        }
+                        None => Err(SystemError::InvalidRequest {
+                            error: "No pool info exists".to_string(),
+                            request: msg.as_slice().into(),
+                        }),
+                    }
+                }
             },
             QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
                 let key: &[u8] = key.as_slice();
@@ -263,10 +390,18 @@ impl WasmMockQuerier {
             token_querier: TokenQuerier::default(),
             tax_querier: TaxQuerier::default(),
             terraswap_factory_querier: TerraswapFactoryQuerier::default(),
+            terraswap_pool_querier: TerraswapPoolQuerier::default(),
+            market_querier: MarketQuerier::default(),
+            oracle_querier: OracleQuerier::default(),
             canonical_length,
         }
     }
 
+    // configure the native balances of an address
+    pub fn with_balance(&mut self, addr: &HumanAddr, balance: &[Coin]) {
+        self.base.update_balance(addr.clone(), balance.to_vec());
+    }
+
     // configure the mint whitelist mock querier
     pub fn with_token_balances(&mut self, balances: &[(&HumanAddr, &[(&HumanAddr, &Uint128)])]) {
         self.token_querier = TokenQuerier::new(balances);
@@ -281,4 +416,19 @@ impl WasmMockQuerier {
     pub fn with_terraswap_pairs(&mut self, pairs: &[(&String, &HumanAddr)]) {
         self.terraswap_factory_querier = TerraswapFactoryQuerier::new(pairs);
     }
+
+    // configure the terraswap pair reserves
+    pub fn with_terraswap_pools(&mut self, pools: &[(&HumanAddr, &[Asset; 2])]) {
+        self.terraswap_pool_querier = TerraswapPoolQuerier::new(pools);
+    }
+
+    // configure the market module swap rates
+    pub fn with_market_rates(&mut self, rates: &[(&String, &String, &Decimal)]) {
+        self.market_querier = MarketQuerier::new(rates);
+    }
+
+    // configure the oracle exchange rates
+    pub fn with_exchange_rates(&mut self, rates: &[(&String, &String, &Decimal)]) {
+        self.oracle_querier = OracleQuerier::new(rates);
+    }
 }
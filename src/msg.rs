@@ -1,57 +1,371 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use cosmwasm_bignumber::Decimal256;
+use cosmwasm_std::{Api, Decimal, Extern, HumanAddr, Querier, StdResult, Storage, Uint128};
+use cw20::Cw20ReceiveMsg;
+use terraswap::asset::{Asset, AssetInfo};
+
+use crate::state::BasketComponentRaw;
 
-use cosmwasm_bignumber::{Decimal256, Uint256};
-use cosmwasm_std::{CanonicalAddr, HumanAddr, Uint128};
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
-    pub alloc_luna: Decimal256,
-    pub alloc_anc: Decimal256,
-    pub alloc_mir: Decimal256,
+    pub basket: Vec<BasketComponent>,
+    /// Can pause operations, but holds no other admin rights
+    pub guardian: Option<HumanAddr>,
     pub eterra_code_id: u64,
     pub stable_denom: String,
     pub terraswap_factory: HumanAddr,
+    /// Default max spread applied to every Terraswap leg without its own limit
+    pub max_spread: Option<Decimal>,
+    /// Deviation from the reference price, in basis points, beyond which mint and
+    /// burn are refused, 0 disables the check
+    pub max_price_deviation: u64,
+    /// Seconds of price history the NAV and token reference prices are averaged
+    /// over, up to a day, 0 uses spot prices. Pricing fails until the history
+    /// covers the window
+    pub twap_window: u64,
+    /// Drift from a target weight, in basis points of the NAV, tolerated before rebalancing
+    pub rebalance_tolerance: u64,
+    /// Seconds after which anyone may rebalance regardless of the tolerance, 0 disables it
+    pub rebalance_interval: u64,
+    /// Paid in the stable denom to whoever pokes a rebalance, up to 1% of the
+    /// value sold
+    pub rebalance_bounty: Uint128,
+    /// Fee taken from each deposit, in basis points
+    pub mint_fee: u64,
+    /// Fee taken from each redemption payout, in basis points
+    pub burn_fee: u64,
+    /// Annual management fee, in basis points, accrued per second by minting eTerra
+    pub management_fee: u64,
+    pub fee_collector: HumanAddr,
+    /// Seconds a config update stays queued before it can be executed
+    pub config_timelock: u64,
+}
+
+/// A single constituent of the index with its target weight in basis points
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BasketComponent {
+    pub info: AssetInfo,
+    pub weight: Decimal256,
+    pub route: SwapRoute,
+}
+
+impl BasketComponent {
+    pub fn to_raw<S: Storage, A: Api, Q: Querier>(
+        &self,
+        deps: &Extern<S, A, Q>,
+    ) -> StdResult<BasketComponentRaw> {
+        Ok(BasketComponentRaw {
+            info: self.info.to_raw(deps)?,
+            weight: self.weight,
+            route: self.route.clone(),
+        })
+    }
+}
+
+/// How a component is traded against the stable denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapRoute {
+    /// Terra market module swap, only valid for native denoms
+    Market {},
+    /// Terraswap pair registered in the configured factory
+    Terraswap {},
+}
+
+/// Bounds the swap of a single component during mint or burn
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SlippageLimit {
+    pub info: AssetInfo,
+    /// Passed to the Terraswap pair, overrides the config default
+    pub max_spread: Option<Decimal>,
+    /// Minimum simulated return of the swap
+    pub min_out: Option<Uint128>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
-    Mint {},
+    Receive(Cw20ReceiveMsg),
+    Mint {
+        limits: Option<Vec<SlippageLimit>>,
+    },
     RegisterETerra {},
-    Burn {},
-    RedeemToken { sender: HumanAddr },
+    /// Mint `amount` eTerra, less the mint fee, for the share of each component
+    /// it represents. Native denoms are taken from the sent funds, any excess is
+    /// refunded, and tokens are pulled with TransferFrom against an allowance.
+    MintInKind {
+        amount: Uint128,
+    },
+    /// Pays `sender` the stable denom gained since `prev_balance` was taken
+    RedeemToken {
+        sender: HumanAddr,
+        prev_balance: Uint128,
+    },
+    /// Books the change in each component balance since `prev_balances` were taken
+    /// into the reserves
+    UpdateReserves {
+        prev_balances: Vec<Asset>,
+    },
+    /// Log where the booked reserves differ from the balances held, open to anyone.
+    /// With `resync` the owner also overwrites the booked reserves with the balances
+    Reconcile {
+        resync: Option<bool>,
+    },
+    /// Queue a config update, replacing any update already queued
+    UpdateConfig(ConfigUpdate),
+    /// Apply the queued config update once its timelock has passed
+    ExecuteQueuedConfig {},
+    CancelQueuedConfig {},
+    /// Hand the guardian role to another key, or revoke it when none is given.
+    /// Applied without the timelock so a leaked key can be replaced at once
+    UpdateGuardian {
+        guardian: Option<HumanAddr>,
+    },
+    /// Nominate a new owner, who must accept before `expires_in_blocks` have passed
+    ProposeNewOwner {
+        owner: HumanAddr,
+        expires_in_blocks: u64,
+    },
+    AcceptOwnership {},
+    CancelOwnershipProposal {},
+    /// Mint the management fee accrued since the last accrual
+    CollectFees {},
+    /// Swap over-weight components into under-weight ones through the stable denom
+    Rebalance {
+        limits: Option<Vec<SlippageLimit>>,
+    },
+    /// Rebalance on behalf of the fund for a bounty, once drift exceeds the tolerance
+    /// or the rebalance interval has elapsed. Every swap must return within
+    /// `max_price_deviation` of the reference price, which must be set
+    PokeRebalance {},
+    /// Record the spot price of every component into the TWAP history, clamped to
+    /// within `max_price_deviation` of the TWAP. Prices closer together than a
+    /// sixteenth of the TWAP window are not recorded
+    RecordPrices {},
+    /// Halt the listed operations, or all of them when none are listed. Also
+    /// available to the guardian
+    Pause {
+        operations: Option<Vec<Operation>>,
+    },
+    /// Resume the listed operations, or all of them when none are listed
+    Unpause {
+        operations: Option<Vec<Operation>>,
+    },
+}
+
+/// Operations that can be paused independently
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Mint,
+    Burn,
+    Rebalance,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Mint, Operation::Burn, Operation::Rebalance];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::Mint => write!(f, "mint"),
+            Operation::Burn => write!(f, "burn"),
+            Operation::Rebalance => write!(f, "rebalance"),
+        }
+    }
+}
+
+/// Owner changes to the config, fields left out are kept as they are. Updates are
+/// timelocked to give eTerra holders a window to exit.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConfigUpdate {
+    pub terraswap_factory: Option<HumanAddr>,
+    pub weights: Option<Vec<ComponentWeight>>,
+    pub max_price_deviation: Option<u64>,
+    pub twap_window: Option<u64>,
+    pub rebalance_tolerance: Option<u64>,
+    pub rebalance_interval: Option<u64>,
+    pub rebalance_bounty: Option<Uint128>,
+    pub mint_fee: Option<u64>,
+    pub burn_fee: Option<u64>,
+    pub management_fee: Option<u64>,
+    pub fee_collector: Option<HumanAddr>,
+    pub config_timelock: Option<u64>,
+}
+
+/// New target weight, in basis points, for an existing basket component
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ComponentWeight {
+    pub info: AssetInfo,
+    pub weight: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    /// Redeem the sent eTerra for its share of the basket
+    Burn { limits: Option<Vec<SlippageLimit>> },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetCount returns the current count as a json-encoded number
     Config {},
     State {},
+    FeeConfig {},
+    PauseFlags {},
+    /// Returns null when no update is queued
+    PendingConfig {},
+    /// Quote a deposit of `amount` stable denom, before any management fee accrued
+    /// since the last accrual
+    SimulateMint {
+        amount: Uint128,
+    },
+    /// Quote redeeming `amount` eTerra, before any management fee accrued since the
+    /// last accrual
+    SimulateBurn {
+        amount: Uint128,
+    },
+    /// Value of the basket held by the fund and the price of eTerra
+    Nav {},
+    /// Spot stable denom price of each basket component, which values the fund
+    /// only while `twap_window` is 0, the TWAP does otherwise
+    Prices {},
+    /// Time-weighted average price of `asset` over `window_seconds` up to its
+    /// latest recorded price, an error when the history does not reach that far back
+    Twap {
+        asset: AssetInfo,
+        window_seconds: u64,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct StateResponse {
     pub total_supply: Uint128,
-    pub reserve_luna: Decimal256,
-    pub reserve_anc: Decimal256,
-    pub reserve_mir: Decimal256,
+    pub reserves: Vec<ReserveResponse>,
+    pub last_fee_accrual: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveResponse {
+    pub info: AssetInfo,
+    pub amount: Decimal256,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ConfigResponse {
     pub owner_addr: HumanAddr,
+    pub guardian: Option<HumanAddr>,
     pub stable_denom: String,
     pub eterra_contract: HumanAddr,
     pub terraswap_factory: HumanAddr,
-    pub alloc_luna: Decimal256,
-    pub alloc_anc: Decimal256,
-    pub alloc_mir: Decimal256,
+    pub basket: Vec<BasketComponent>,
+    pub max_spread: Option<Decimal>,
+    pub max_price_deviation: u64,
+    pub twap_window: u64,
+    pub rebalance_tolerance: u64,
+    pub rebalance_interval: u64,
+    pub rebalance_bounty: Uint128,
+    pub config_timelock: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingConfigResponse {
+    pub update: ConfigUpdate,
+    pub execute_after: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseFlagsResponse {
+    pub mint: bool,
+    pub burn: bool,
+    pub rebalance: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeConfigResponse {
+    pub mint_fee: u64,
+    pub burn_fee: u64,
+    pub management_fee: u64,
+    pub fee_collector: HumanAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateMintResponse {
+    /// eTerra minted for the deposit
+    pub mint_amount: Uint128,
+    /// Tax paid on sending the fee and each swap
+    pub tax_amount: Uint128,
+    pub fee_amount: Uint128,
+    /// Stable denom => component swaps
+    pub swaps: Vec<SwapRateResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateBurnResponse {
+    /// Stable denom paid out for the burnt eTerra
+    pub return_amount: Uint128,
+    /// Tax deducted from the swap returns and the payout
+    pub tax_amount: Uint128,
+    pub fee_amount: Uint128,
+    /// Component => stable denom swaps
+    pub swaps: Vec<SwapRateResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PricesResponse {
+    pub prices: Vec<PriceResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceResponse {
+    pub info: AssetInfo,
+    /// Stable denom per unit of the component
+    pub price: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TwapResponse {
+    pub info: AssetInfo,
+    pub price: Decimal256,
+    /// `end_time` less the requested window
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NavResponse {
+    /// Value of the held components, in the stable denom
+    pub nav: Uint128,
+    pub total_supply: Uint128,
+    /// Stable denom per eTerra, 1 while nothing has been minted
+    pub price: Decimal256,
+    pub components: Vec<ComponentNavResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ComponentNavResponse {
+    pub info: AssetInfo,
+    pub balance: Uint128,
+    /// Stable denom per unit of the component
+    pub price: Decimal256,
+    pub value: Uint128,
+    /// Share of the NAV, in basis points
+    pub weight: Decimal256,
+    pub target_weight: Decimal256,
 }
 
+/// Simulated swap of a single basket component
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct SwapRateResponse {
+    pub info: AssetInfo,
+    pub offer_amount: Uint128,
     pub return_amount: Decimal256,
     pub spread_amount: Decimal256,
     pub commission_amount: Decimal256,
+    pub tax_amount: Decimal256,
 }
@@ -0,0 +1,229 @@
+use cosmwasm_bignumber::{Decimal256, Uint256};
+use cosmwasm_std::{Api, Coin, Extern, HumanAddr, Querier, StdResult, Storage, Uint128};
+use terra_cosmwasm::TerraQuerier;
+use terraswap::asset::AssetInfo;
+
+use crate::{
+    error::ContractError,
+    math::decimal_ratio,
+    msg::{BasketComponent, SwapRoute},
+    querier::{query_component_balance, query_component_pool},
+    state::{read_price_history, store_price_history, Config},
+};
+
+/// One whole token of a 6 decimal asset
+const PRICE_UNIT: u128 = 1_000_000;
+/// Furthest a recorded price can sit from the TWAP while `max_price_deviation` is
+/// 0, in basis points
+const DEFAULT_MAX_OBSERVATION_CHANGE: u64 = 1000;
+
+/// Stable denom price of a single unit of `info`. Native denoms are priced by the
+/// Terra oracle, tokens at the mid-price of their Terraswap pair.
+pub fn query_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    info: &AssetInfo,
+) -> StdResult<Decimal256> {
+    match info {
+        AssetInfo::NativeToken { denom } => {
+            if denom == stable_denom {
+                return Ok(Decimal256::one());
+            }
+
+            let terra_querier = TerraQuerier::new(&deps.querier);
+            let res = terra_querier
+                .query_exchange_rates(denom.to_string(), vec![stable_denom.to_string()])?;
+            res.exchange_rates
+                .iter()
+                .find(|rate| rate.quote_denom == stable_denom)
+                .map(|rate| Decimal256::from(rate.exchange_rate))
+                .ok_or_else(|| {
+                    ContractError::PriceNotFound {
+                        asset: denom.to_string(),
+                    }
+                    .into()
+                })
+        }
+        AssetInfo::Token { .. } => {
+            let (stable_pool, component_pool) =
+                query_component_pool(deps, terraswap_factory, stable_denom, info)?;
+            if component_pool.is_zero() {
+                return Ok(Decimal256::zero());
+            }
+
+            Ok(decimal_ratio(stable_pool, component_pool))
+        }
+    }
+}
+
+/// Price a component currently trades at along its route: the market module swap
+/// rate of `PRICE_UNIT` or the Terraswap pair mid-price
+pub fn query_route_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    component: &BasketComponent,
+) -> StdResult<Decimal256> {
+    match (&component.route, &component.info) {
+        (SwapRoute::Market {}, AssetInfo::NativeToken { denom }) => {
+            let terra_querier = TerraQuerier::new(&deps.querier);
+            let res = terra_querier.query_swap(
+                Coin {
+                    denom: denom.to_string(),
+                    amount: Uint128(PRICE_UNIT),
+                },
+                stable_denom,
+            )?;
+            Ok(decimal_ratio(res.receive.amount, Uint128(PRICE_UNIT)))
+        }
+        (SwapRoute::Market {}, AssetInfo::Token { .. }) => {
+            Err(ContractError::MarketRouteNotNative {
+                asset: component.info.to_string(),
+            }
+            .into())
+        }
+        (SwapRoute::Terraswap {}, info) => {
+            let (stable_pool, component_pool) =
+                query_component_pool(deps, terraswap_factory, stable_denom, info)?;
+            if component_pool.is_zero() {
+                return Ok(Decimal256::zero());
+            }
+
+            Ok(decimal_ratio(stable_pool, component_pool))
+        }
+    }
+}
+
+/// Price independent of the component route to check it against: the oracle for
+/// native denoms and the TWAP for tokens, which have none while `twap_window` is 0
+pub fn query_reference_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    config: &Config,
+    info: &AssetInfo,
+    time: Option<u64>,
+) -> StdResult<Option<Decimal256>> {
+    match info {
+        AssetInfo::NativeToken { .. } => Ok(Some(query_price(
+            deps,
+            terraswap_factory,
+            &config.stable_denom,
+            info,
+        )?)),
+        AssetInfo::Token { .. } => query_twap_price(deps, config, info, time),
+    }
+}
+
+/// Average of the recorded prices of `info` over `twap_window` up to `time`, or
+/// up to the latest observation when there is no time to go by. None while
+/// `twap_window` is 0 or the history does not cover the window.
+pub fn query_twap_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &Config,
+    info: &AssetInfo,
+    time: Option<u64>,
+) -> StdResult<Option<Decimal256>> {
+    if config.twap_window == 0 {
+        return Ok(None);
+    }
+
+    let info_raw = info.to_raw(deps)?;
+    let history = read_price_history(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    let twap = history
+        .iter()
+        .find(|h| h.info.equal(&info_raw))
+        .and_then(|h| {
+            let end = time.or_else(|| h.observations.last().map(|o| o.timestamp))?;
+            h.twap(end, config.twap_window)
+        })
+        .map(|(price, _)| price);
+
+    Ok(twap)
+}
+
+/// Price the basket is valued at: the TWAP when one is kept, the spot price only
+/// while `twap_window` is 0
+pub fn query_valuation_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    config: &Config,
+    info: &AssetInfo,
+    time: Option<u64>,
+) -> StdResult<Decimal256> {
+    if config.twap_window == 0 {
+        return query_price(deps, terraswap_factory, &config.stable_denom, info);
+    }
+
+    query_twap_price(deps, config, info, time)?.ok_or_else(|| {
+        ContractError::NoPriceHistory {
+            asset: info.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Values `amount` of `info` in the stable denom at its `query_valuation_price`
+pub fn query_value<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    config: &Config,
+    info: &AssetInfo,
+    amount: Uint128,
+    time: Option<u64>,
+) -> StdResult<Uint128> {
+    if amount.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    let price = query_valuation_price(deps, terraswap_factory, config, info, time)?;
+    Ok((Uint256::from(amount) * price).into())
+}
+
+/// Net asset value of the basket held by `contract_addr`, in the stable denom
+pub fn query_nav<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_addr: &HumanAddr,
+    config: &Config,
+    time: Option<u64>,
+) -> StdResult<Uint128> {
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+    let mut nav = Uint128::zero();
+    for component in config.basket.iter() {
+        let info = component.info.to_normal(deps)?;
+        let balance = query_component_balance(deps, contract_addr, &info)?;
+        nav += query_value(deps, &terraswap_factory, config, &info, balance, time)?;
+    }
+
+    Ok(nav)
+}
+
+/// Records the spot price of every component at `time`, returning the prices kept.
+/// Each is clamped to `max_price_deviation` of the TWAP, the bound the circuit
+/// breaker holds pools to, or to `DEFAULT_MAX_OBSERVATION_CHANGE` while none is set.
+pub fn record_prices<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    config: &Config,
+    time: u64,
+) -> StdResult<Vec<(AssetInfo, Decimal256)>> {
+    let terraswap_factory = deps.api.human_address(&config.terraswap_factory)?;
+    let mut history = read_price_history(&deps.storage).load()?;
+    let max_change = match config.max_price_deviation {
+        0 => Decimal256::from_ratio(DEFAULT_MAX_OBSERVATION_CHANGE, 10000u64),
+        deviation => Decimal256::from_ratio(deviation, 10000u64),
+    };
+
+    let mut prices = vec![];
+    for component in history.iter_mut() {
+        let info = component.info.to_normal(deps)?;
+        let price = query_price(deps, &terraswap_factory, &config.stable_denom, &info)?;
+        if let Some(price) = component.record(time, price, config.twap_window, max_change) {
+            prices.push((info, price));
+        }
+    }
+
+    store_price_history(&mut deps.storage).save(&history)?;
+    Ok(prices)
+}
@@ -0,0 +1,130 @@
+use cosmwasm_std::{
+    to_binary, Api, Coin, Extern, HumanAddr, Querier, QueryRequest, StdResult, Storage, Uint128,
+    WasmQuery,
+};
+use terra_cosmwasm::TerraQuerier;
+use terraswap::{
+    asset::{Asset, AssetInfo, PairInfo},
+    pair::{PoolResponse, QueryMsg as PairQueryMsg, SimulationResponse},
+    querier::{query_balance, query_pair_info, query_token_balance, simulate},
+};
+
+use crate::{
+    error::ContractError,
+    msg::{BasketComponent, SwapRoute},
+};
+
+pub fn query_component_pair<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    info: &AssetInfo,
+) -> Result<PairInfo, ContractError> {
+    query_pair_info(
+        deps,
+        terraswap_factory,
+        &[
+            AssetInfo::NativeToken {
+                denom: stable_denom.to_string(),
+            },
+            info.clone(),
+        ],
+    )
+    .map_err(|_| ContractError::PairNotFound {
+        asset: info.to_string(),
+    })
+}
+
+pub fn query_component_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account_addr: &HumanAddr,
+    info: &AssetInfo,
+) -> StdResult<Uint128> {
+    match info {
+        AssetInfo::NativeToken { denom } => query_balance(deps, account_addr, denom.to_string()),
+        AssetInfo::Token { contract_addr } => {
+            query_token_balance(deps, contract_addr, account_addr)
+        }
+    }
+}
+
+/// Stable denom and component reserves of the component's Terraswap pair
+pub fn query_component_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    info: &AssetInfo,
+) -> StdResult<(Uint128, Uint128)> {
+    let pair_info = query_component_pair(deps, terraswap_factory, stable_denom, info)?;
+    let pool: PoolResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: pair_info.contract_addr,
+        msg: to_binary(&PairQueryMsg::Pool {})?,
+    }))?;
+
+    let stable_info = AssetInfo::NativeToken {
+        denom: stable_denom.to_string(),
+    };
+    let stable_pool = pool
+        .assets
+        .iter()
+        .find(|a| a.info.equal(&stable_info))
+        .map(|a| a.amount)
+        .unwrap_or_else(Uint128::zero);
+    let component_pool = pool
+        .assets
+        .iter()
+        .find(|a| a.info.equal(info))
+        .map(|a| a.amount)
+        .unwrap_or_else(Uint128::zero);
+
+    Ok((stable_pool, component_pool))
+}
+
+/// Simulates swapping `offer_asset` between the stable denom and `component`
+/// along the component route. Market swaps report their spread inside the return.
+pub fn simulate_component_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    terraswap_factory: &HumanAddr,
+    stable_denom: &str,
+    component: &BasketComponent,
+    offer_asset: &Asset,
+) -> StdResult<SimulationResponse> {
+    match &component.route {
+        SwapRoute::Market {} => {
+            let (offer_denom, ask_denom) = match (&offer_asset.info, &component.info) {
+                (AssetInfo::NativeToken { denom }, AssetInfo::NativeToken { denom: component })
+                    if denom == stable_denom =>
+                {
+                    (denom.to_string(), component.to_string())
+                }
+                (AssetInfo::NativeToken { denom }, AssetInfo::NativeToken { .. }) => {
+                    (denom.to_string(), stable_denom.to_string())
+                }
+                _ => {
+                    return Err(ContractError::MarketRouteNotNative {
+                        asset: component.info.to_string(),
+                    }
+                    .into())
+                }
+            };
+            let terra_querier = TerraQuerier::new(&deps.querier);
+            let res = terra_querier.query_swap(
+                Coin {
+                    denom: offer_denom,
+                    amount: offer_asset.amount,
+                },
+                ask_denom,
+            )?;
+            Ok(SimulationResponse {
+                return_amount: res.receive.amount,
+                spread_amount: Uint128::zero(),
+                commission_amount: Uint128::zero(),
+            })
+        }
+        SwapRoute::Terraswap {} => {
+            let pair_info =
+                query_component_pair(deps, terraswap_factory, stable_denom, &component.info)?;
+            simulate(deps, &pair_info.contract_addr, offer_asset)
+        }
+    }
+}
@@ -1,44 +1,278 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_bignumber::Decimal256;
-use cosmwasm_std::{CanonicalAddr, Storage, Uint128};
+use cosmwasm_bignumber::{Decimal256, Uint256};
+use cosmwasm_std::{Api, CanonicalAddr, Decimal, Extern, Querier, StdResult, Storage, Uint128};
 use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use terraswap::asset::AssetInfoRaw;
+
+use crate::msg::{BasketComponent, ConfigUpdate, Operation, SwapRoute};
 
 const KEY_CONFIG: &[u8] = b"config";
 const KEY_STATE: &[u8] = b"state";
+const KEY_PENDING_OWNER: &[u8] = b"pending_owner";
+const KEY_PAUSE_FLAGS: &[u8] = b"pause_flags";
+const KEY_PENDING_CONFIG: &[u8] = b"pending_config";
+const KEY_PRICE_HISTORY: &[u8] = b"price_history";
+
+/// Observations kept per component, older ones are dropped
+const MAX_PRICE_OBSERVATIONS: usize = 32;
+/// Observations are spaced at least a window over this apart, so the history kept
+/// spans about twice the window however often prices are recorded
+const OBSERVATIONS_PER_WINDOW: u64 = 16;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner_addr: CanonicalAddr,
+    /// Can pause operations, but holds no other admin rights
+    pub guardian: Option<CanonicalAddr>,
+    /// Address of this contract, queries have no env to read it from
+    pub contract_addr: CanonicalAddr,
     pub stable_denom: String,
     pub eterra_contract: CanonicalAddr,
     pub terraswap_factory: CanonicalAddr,
-    pub alloc_luna: Decimal256,
-    pub alloc_anc: Decimal256,
-    pub alloc_mir: Decimal256,
+    pub basket: Vec<BasketComponentRaw>,
+    pub max_spread: Option<Decimal>,
+    /// Deviation from the reference price, in basis points, beyond which mint and
+    /// burn are refused, 0 disables the check
+    pub max_price_deviation: u64,
+    /// Seconds of price history the NAV and token reference prices are averaged
+    /// over, 0 uses spot prices
+    pub twap_window: u64,
+    /// Drift from a target weight, in basis points of the NAV, tolerated before rebalancing
+    pub rebalance_tolerance: u64,
+    /// Seconds after which anyone may rebalance regardless of the tolerance, 0 disables it
+    pub rebalance_interval: u64,
+    /// Paid in the stable denom to whoever pokes a rebalance, up to 1% of the
+    /// value sold
+    pub rebalance_bounty: Uint128,
+    /// Fee on deposits, in basis points
+    pub mint_fee: u64,
+    /// Fee on redemptions, in basis points
+    pub burn_fee: u64,
+    /// Annual management fee, in basis points, minted as eTerra to the fee collector
+    pub management_fee: u64,
+    pub fee_collector: CanonicalAddr,
+    /// Seconds a config update stays queued before it can be executed
+    pub config_timelock: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BasketComponentRaw {
+    pub info: AssetInfoRaw,
+    pub weight: Decimal256,
+    pub route: SwapRoute,
+}
+
+impl BasketComponentRaw {
+    pub fn to_normal<S: Storage, A: Api, Q: Querier>(
+        &self,
+        deps: &Extern<S, A, Q>,
+    ) -> StdResult<BasketComponent> {
+        Ok(BasketComponent {
+            info: self.info.to_normal(deps)?,
+            weight: self.weight,
+            route: self.route.clone(),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub total_supply: Uint128,
-    pub reserve_luna: Decimal256,
-    pub reserve_anc: Decimal256,
-    pub reserve_mir: Decimal256,
+    pub reserves: Vec<Reserve>,
+    /// Block time up to which the management fee has been minted
+    pub last_fee_accrual: u64,
+    /// Block time of the last rebalance
+    pub last_rebalance: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Reserve {
+    pub info: AssetInfoRaw,
+    pub amount: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwner {
+    pub owner: CanonicalAddr,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingConfig {
+    pub update: ConfigUpdate,
+    /// Block time from which the update can be executed
+    pub execute_after: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct PauseFlags {
+    pub mint: bool,
+    pub burn: bool,
+    pub rebalance: bool,
+}
+
+impl PauseFlags {
+    pub fn is_paused(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::Mint => self.mint,
+            Operation::Burn => self.burn,
+            Operation::Rebalance => self.rebalance,
+        }
+    }
+
+    pub fn set(&mut self, operation: Operation, paused: bool) {
+        match operation {
+            Operation::Mint => self.mint = paused,
+            Operation::Burn => self.burn = paused,
+            Operation::Rebalance => self.rebalance = paused,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceHistory {
+    pub info: AssetInfoRaw,
+    pub observations: Vec<PriceObservation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceObservation {
+    pub timestamp: u64,
+    /// Spot price recorded at `timestamp` as clamped to the TWAP, in effect until
+    /// the next observation
+    pub price: Decimal256,
+    /// Sum of price times seconds elapsed over all earlier observations
+    pub cumulative: Decimal256,
 }
 
-pub fn store_config<S: Storage>(storage: &mut S) -> Singleton<S, Config> {
+impl PriceHistory {
+    /// Records `price` at `time`, returning the price kept if any. None is kept
+    /// closer than `window / OBSERVATIONS_PER_WINDOW` to the last observation, so
+    /// recording every block cannot crowd the window out of the history. A spot
+    /// price can be pushed for the one call that records it, so the price kept is
+    /// clamped to within `max_change` (at most one) of the TWAP up to `time`, or of
+    /// the last observation while the history is shorter than the window. Each
+    /// pushed observation then moves the TWAP by a sixteenth of `max_change` at
+    /// most, however far the pool was pushed.
+    pub fn record(
+        &mut self,
+        time: u64,
+        price: Decimal256,
+        window: u64,
+        max_change: Decimal256,
+    ) -> Option<Decimal256> {
+        let min_interval = window / OBSERVATIONS_PER_WINDOW;
+        let last = match self.observations.last() {
+            Some(last) if time <= last.timestamp || time - last.timestamp < min_interval => {
+                return None
+            }
+            last => last,
+        };
+        let cumulative = last.map_or_else(Decimal256::zero, |last| {
+            last.cumulative + last.price * seconds(time - last.timestamp)
+        });
+
+        let reference = self
+            .twap(time, window)
+            .map(|(twap, _)| twap)
+            .or_else(|| last.map(|last| last.price));
+        let price = match reference {
+            Some(reference) if price > reference + reference * max_change => {
+                reference + reference * max_change
+            }
+            Some(reference) if price < reference - reference * max_change => {
+                reference - reference * max_change
+            }
+            _ => price,
+        };
+
+        self.observations.push(PriceObservation {
+            timestamp: time,
+            price,
+            cumulative,
+        });
+        if self.observations.len() > MAX_PRICE_OBSERVATIONS {
+            self.observations.remove(0);
+        }
+        Some(price)
+    }
+
+    /// Time-weighted average price over the `window` seconds up to `end`, with the
+    /// time it starts from. None unless the history reaches back over the whole
+    /// window, a shorter one would weigh the latest prices more than configured.
+    pub fn twap(&self, end: u64, window: u64) -> Option<(Decimal256, u64)> {
+        let start = end.checked_sub(window)?;
+        if self.observations.first()?.timestamp > start {
+            return None;
+        }
+        if window == 0 {
+            return self.observation_at(end).map(|o| (o.price, start));
+        }
+
+        let cumulative = self.cumulative_at(end)? - self.cumulative_at(start)?;
+        Some((cumulative / seconds(window), start))
+    }
+
+    fn observation_at(&self, time: u64) -> Option<&PriceObservation> {
+        self.observations.iter().rev().find(|o| o.timestamp <= time)
+    }
+
+    fn cumulative_at(&self, time: u64) -> Option<Decimal256> {
+        self.observation_at(time)
+            .map(|o| o.cumulative + o.price * seconds(time - o.timestamp))
+    }
+}
+
+fn seconds(seconds: u64) -> Decimal256 {
+    Decimal256::from_uint256(Uint256::from(seconds))
+}
+
+pub fn store_config<S: Storage>(storage: &mut S) -> Singleton<'_, S, Config> {
     singleton(storage, KEY_CONFIG)
 }
 
-pub fn read_config<S: Storage>(storage: &S) -> ReadonlySingleton<S, Config> {
+pub fn read_config<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, Config> {
     singleton_read(storage, KEY_CONFIG)
 }
 
-pub fn store_state<S: Storage>(storage: &mut S) -> Singleton<S, State> {
+pub fn store_state<S: Storage>(storage: &mut S) -> Singleton<'_, S, State> {
     singleton(storage, KEY_STATE)
 }
 
-pub fn read_state<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
+pub fn read_state<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, State> {
     singleton_read(storage, KEY_STATE)
 }
+
+pub fn store_pending_owner<S: Storage>(storage: &mut S) -> Singleton<'_, S, PendingOwner> {
+    singleton(storage, KEY_PENDING_OWNER)
+}
+
+pub fn read_pending_owner<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, PendingOwner> {
+    singleton_read(storage, KEY_PENDING_OWNER)
+}
+
+pub fn store_pause_flags<S: Storage>(storage: &mut S) -> Singleton<'_, S, PauseFlags> {
+    singleton(storage, KEY_PAUSE_FLAGS)
+}
+
+pub fn read_pause_flags<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, PauseFlags> {
+    singleton_read(storage, KEY_PAUSE_FLAGS)
+}
+
+pub fn store_pending_config<S: Storage>(storage: &mut S) -> Singleton<'_, S, PendingConfig> {
+    singleton(storage, KEY_PENDING_CONFIG)
+}
+
+pub fn read_pending_config<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, PendingConfig> {
+    singleton_read(storage, KEY_PENDING_CONFIG)
+}
+
+pub fn store_price_history<S: Storage>(storage: &mut S) -> Singleton<'_, S, Vec<PriceHistory>> {
+    singleton(storage, KEY_PRICE_HISTORY)
+}
+
+pub fn read_price_history<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, Vec<PriceHistory>> {
+    singleton_read(storage, KEY_PRICE_HISTORY)
+}